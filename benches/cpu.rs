@@ -0,0 +1,88 @@
+use std::hint::black_box;
+
+use cassowary_gb::console::cpu::instruction::Instruction;
+use cassowary_gb::console::cpu::SharpSM83;
+use cassowary_gb::console::memory::{Memory, KBYTE};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+// 154 lines of 456 dots
+const DOTS_PER_FRAME: usize = 70224;
+
+// the tables against decoding from the bit fields on every fetch
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.bench_function("fields", |b| {
+        b.iter(|| (0..=u8::MAX).for_each(|opcode| {
+            black_box(Instruction::decode_fields(black_box(opcode)));
+        }))
+    });
+    group.bench_function("table", |b| {
+        b.iter(|| (0..=u8::MAX).for_each(|opcode| {
+            black_box(Instruction::decode(black_box(opcode)));
+        }))
+    });
+    group.bench_function("cb_fields", |b| {
+        b.iter(|| (0..=u8::MAX).for_each(|opcode| {
+            black_box(Instruction::decode_cb_fields(black_box(opcode)));
+        }))
+    });
+    group.bench_function("cb_table", |b| {
+        b.iter(|| (0..=u8::MAX).for_each(|opcode| {
+            black_box(Instruction::decode_cb(black_box(opcode)));
+        }))
+    });
+    group.finish();
+}
+
+// loads, alu ops, a prefixed op and jumps over work ram, like a game's inner loop
+const PROGRAM: [u8; 17] = [
+    0x21, 0x00, 0xC0, // ld hl, 0xC000
+    0x06, 0x00, // ld b, 0
+    0x7E, // ld a, (hl)
+    0x80, // add b
+    0x77, // ld (hl), a
+    0xCB, 0x37, // swap a
+    0x23, // inc hl
+    0x05, // dec b
+    0x20, 0xF7, // jr nz, -9
+    0xC3, 0x00, 0x00, // jp 0x0000
+];
+
+fn run(c: &mut Criterion) {
+    let mut cpu = SharpSM83::new();
+    let mut memory = Memory::new(8 * KBYTE);
+    for (addr, byte) in PROGRAM.iter().enumerate() {
+        memory.write(addr as u16, *byte);
+    }
+    let mut group = c.benchmark_group("run 1000 instructions");
+    group.bench_function("table", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                black_box(cpu.run(&mut memory).unwrap());
+            }
+        })
+    });
+    group.bench_function("fields", |b| {
+        b.iter(|| {
+            for _ in 0..1000 {
+                black_box(cpu.run_with(&mut memory, Instruction::decode_fields).unwrap());
+            }
+        })
+    });
+    group.finish();
+}
+
+// the rest of the machine through one frame, every bus access ticks it
+fn tick(c: &mut Criterion) {
+    let mut memory = Memory::new(8 * KBYTE);
+    c.bench_function("tick a frame", |b| {
+        b.iter(|| {
+            for _ in 0..DOTS_PER_FRAME {
+                memory.tick();
+            }
+        })
+    });
+}
+
+criterion_group!(benches, decode, run, tick);
+criterion_main!(benches);
@@ -6,12 +6,33 @@ pub mod joypad;
 pub mod regids;
 pub mod apu;
 pub mod interrupts;
+pub mod cartridge;
+pub mod serial;
+pub mod dma;
+pub mod mbc;
+pub mod save;
+pub mod boot;
+pub mod tilt;
+pub mod loader;
+pub mod patch;
+pub mod cheat;
+pub mod gbx;
+pub mod dat;
+pub mod power;
 
-use crate::ppu::*;
 use crate::cpu::*;
 use crate::memory::*;
-use crate::timer::*;
-use crate::joypad::*;
+use crate::cartridge::*;
+use crate::mbc::CartridgeEvent;
+use crate::mbc::sensor::ImageSource;
+use crate::save::*;
+use crate::boot::*;
+use crate::tilt::Tilt;
+use crate::loader::*;
+use crate::cheat::*;
+use crate::dat::*;
+use crate::gbx::GbxFooter;
+use crate::power::PowerOnState;
 
 
 use core::fmt;
@@ -30,10 +51,9 @@ use std::thread::JoinHandle;
 use std::time::{Instant, Duration};
 use std::cmp::min;
 use std::collections::HashSet;
-use std::fs::read;
+use std::path::Path;
 use std::u128;
 
-use interrupts::SERIAL_I;
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
 use sdl2::pixels::PixelFormatEnum;
@@ -46,6 +66,8 @@ pub const LCD_HEIGHT: usize = 144;
 pub const LCD_SIZE: usize = LCD_WIDTH*LCD_HEIGHT;
 const SCREEN_WIDTH: u32 = LCD_WIDTH as u32 * 4;
 const SCREEN_HEIGHT: u32 = LCD_HEIGHT as u32 * 4;
+// how often battery ram gets written out while running
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Clone)]
 struct PerfError {
@@ -102,45 +124,21 @@ impl<const N: usize> PerfTimer<N> {
 
 
 
-pub struct GBIO {
-    timer: HTimer,
-    joypad: Joypad,
-    pub ppu: PPU,
-    //serial: SerialPort,
-}
-
-impl GBIO {
-    pub fn new() -> GBIO {
-        GBIO {
-            timer: HTimer::new(),
-            joypad: Joypad::default(),
-            ppu: PPU::new(),
-        }
-    }
-
-    pub fn update(&mut self, memory: &mut Memory, keys: &HashSet<Keycode>) -> Result<(), String> {
-        self.joypad.update(memory, &keys);
-        self.timer.update(false, memory);
-        self.ppu.update(memory);
-        if memory.read(0xFF02) == 0x81 {
-            memory.request_interrupt(SERIAL_I);
-            memory.write_io(0xFF02, 0x01);
-            print!("{}", memory.read(0xFF01) as char);
-            memory.write_io(0xFF01, 0xFF);
-        } else if memory.read(0xFF02) == 0x80 {
-            //self.gamepack.request_interrupt(SERIAL_I);
-            //self.gamepack.write_io(0xFF02, 0x00);
-            //self.gamepack.write_io(0xFF01, 0x00);
-            //print!("{}", self.gamepack.read(0xFF01) as char);
-        }
-
-        Ok(())
-    }
-
-}
-
 pub struct GameBoy {
     pub gamepack: Arc<Mutex<Memory>>,
+    header: Option<CartridgeHeader>,
+    identity: Option<RomIdentity>,
+    save_file: Option<SaveFile>,
+    event_handler: Option<Box<dyn FnMut(CartridgeEvent)>>,
+    // None starts at 0x0100 in the state the boot rom of `model` leaves behind
+    boot_rom: Option<Vec<u8>>,
+    model: Model,
+    power_on: PowerOnState,
+    lockup_action: LockupAction,
+    // why the cpu locked up or stopped during the last `run_emu`
+    cpu_error: Option<CpuError>,
+    // applied instead of the ones found next to the rom when not empty
+    patches: Vec<std::path::PathBuf>,
     log_memory: bool,
 }
 
@@ -150,10 +148,61 @@ impl GameBoy {
         let memory = Arc::new(Mutex::new(Memory::new(8 * KBYTE)));
         GameBoy {
             gamepack: memory,
+            header: None,
+            identity: None,
+            save_file: None,
+            event_handler: None,
+            boot_rom: None,
+            model: Model::default(),
+            power_on: PowerOnState::default(),
+            lockup_action: LockupAction::default(),
+            cpu_error: None,
+            patches: Vec::new(),
             log_memory,
         }
     }
 
+    /// Boot rom to run before the cartridge, None skips it
+    pub fn set_boot_rom(&mut self, path: Option<&Path>) -> std::io::Result<()> {
+        self.boot_rom = match path {
+            Some(path) => Some(load_boot_rom(path)?),
+            None => None,
+        };
+        Ok(())
+    }
+
+    /// Model whose post boot state is used when the boot rom is skipped
+    pub fn set_model(&mut self, model: Model) {
+        self.model = model;
+    }
+
+    /// What ram and registers hold when `run_emu` powers on, zeros by default
+    pub fn set_power_on(&mut self, state: PowerOnState) {
+        self.power_on = state;
+    }
+
+    /// Power on state with its seed, passing it back to `set_power_on` repeats a run
+    pub fn power_on(&self) -> PowerOnState {
+        self.power_on
+    }
+
+    /// Whether an illegal opcode locks the cpu up like hardware or stops `run_emu` with the error
+    pub fn set_lockup_action(&mut self, action: LockupAction) {
+        self.lockup_action = action;
+    }
+
+    /// The illegal opcode that locked the cpu up or stopped the last `run_emu`, None while it
+    /// ran fine
+    pub fn cpu_error(&self) -> Option<CpuError> {
+        self.cpu_error
+    }
+
+    /// IPS, UPS or BPS patches applied in order by the next load, empty uses
+    /// the first one sharing the rom's name
+    pub fn set_patches(&mut self, patches: Vec<std::path::PathBuf>) {
+        self.patches = patches;
+    }
+
     fn run_cpu(&mut self, stop_signal: Arc<AtomicBool>, clock: Arc<(Mutex<usize>, Condvar)>) -> thread::JoinHandle<()> {
         let mut clock_timer = Instant::now();
 
@@ -182,7 +231,7 @@ impl GameBoy {
                 
                 if cpu_cycles == *clock_cycles_guard {
                     counter += 1;
-                    cpu_cycles += cpu.run(&mut memory);
+                    cpu_cycles += cpu.run(&mut memory).unwrap_or(0);
                     println!("cpu: {clock_cycles_guard}");
                 }
                 clock_timer = Instant::now();
@@ -217,40 +266,53 @@ impl GameBoy {
         let mut event_pump = sdl_context.event_pump().unwrap();
 
         let mut render_timer = Instant::now();
+        let mut save_timer = Instant::now();
+        let mut key_tilt = Tilt::default();
 
-        /*
-         * I/O Devices
-         */
-        let mut io_devices = GBIO::new();
         //let keys: Arc<Mutex<HashSet<Keycode>>> = Arc::new(Mutex::new(HashSet::new()));
-        let mut keys: HashSet<Keycode> = HashSet::new();
         let stop = Arc::new(AtomicBool::new(false));
 
         /*
          * Timing 
          */
         //let clock = Arc::new((Mutex::new(0), Condvar::new()));
-        let mut clock_cycles = 0;
         //let cpu_handle = self.run_cpu(stop.clone(), Arc::clone(&clock));
         
-        let mut cpu_cycles: usize = 0;
+        // dots the last instruction took, the next one waits that long
+        let mut cpu_dots: u64 = 0;
         let mut cpu = SharpSM83::new();
+        cpu.set_lockup_action(self.lockup_action);
+        self.cpu_error = None;
+        {
+            let mut memory = self.gamepack.lock().unwrap();
+            memory.power_on(&self.power_on);
+            cpu.power_on(&self.power_on);
+            match &self.boot_rom {
+                Some(boot_rom) => memory.load_boot_rom(boot_rom.clone()).map_err(|e| e.to_string())?,
+                None => {
+                    memory.skip_boot(self.model);
+                    cpu.skip_boot(self.model, &memory);
+                }
+            }
+        }
 
         let mut clock_timer = Instant::now();
         'running: loop {
-            if clock_timer.elapsed() > Duration::from_nanos(CLOCK_RATE_NANOS) {
-                let mut memory = &mut *self.gamepack.lock().unwrap();
-                cpu.update(&mut memory);
-                if cpu_cycles == clock_cycles {
-                    cpu_cycles += cpu.run(&mut memory);
-                }
-                // threads enters here
-                io_devices.update(memory, &keys).unwrap();
-                //let (lock, cvar) = &*clock; 
-                //let mut clock_cycles = lock.lock().unwrap();
-                clock_cycles += 1;
-                //cvar.notify_one();
-                //println!("{clock_cycles}");
+            if clock_timer.elapsed() > Duration::from_nanos(CLOCK_RATE_NANOS * cpu_dots) {
+                let memory = &mut *self.gamepack.lock().unwrap();
+                cpu.update(memory);
+                // the cpu ticks the rest of the machine on every bus access
+                cpu_dots = match cpu.run(memory) {
+                    Ok(m_cycles) => (m_cycles * memory.m_cycle_dots()) as u64,
+                    Err(error) => {
+                        self.cpu_error = Some(error);
+                        // a locked up cpu keeps the rest of the machine running
+                        match cpu.is_locked() {
+                            true => 0,
+                            false => break 'running,
+                        }
+                    }
+                };
                 clock_timer = Instant::now();
             }
             /*
@@ -270,26 +332,55 @@ impl GameBoy {
                     }
                 }
 
-                keys = event_pump
+                let keys: HashSet<Keycode> = event_pump
                     .keyboard_state()
                     .pressed_scancodes()
                     .filter_map(Keycode::from_scancode)
                     .collect();
-                io_devices.ppu.render(&mut texture)?;
+                // keys only take over the tilt when they change, so set_tilt sticks otherwise
+                let tilt = Tilt::from_keys(&keys);
+                if tilt != key_tilt {
+                    self.set_tilt(tilt);
+                    key_tilt = tilt;
+                }
+                let mut memory = self.gamepack.lock().unwrap();
+                memory.update_joypad(&keys);
+                memory.apply_cheats();
+                memory.ppu.render(&mut texture)?;
+                drop(memory);
+
+                for event in self.take_cartridge_events() {
+                    if let Some(handler) = &mut self.event_handler {
+                        handler(event);
+                    }
+                }
 
                 canvas.copy(&texture, None, None)?;
                 canvas.present();
 
                 render_timer = Instant::now();
             }
+
+            if save_timer.elapsed() > SAVE_INTERVAL {
+                if let Err(error) = self.flush_save() {
+                    println!("{error}, could not write save file");
+                }
+                save_timer = Instant::now();
+            }
         }
 
         //cpu_handle.join().unwrap();
         self.stop();
-        Ok(())
+        match self.cpu_error {
+            Some(error) if !cpu.is_locked() => Err(format!("cpu stopped, {error}")),
+            _ => Ok(()),
+        }
     }
 
-    pub fn stop(&self) {    
+    pub fn stop(&mut self) {
+        if let Err(error) = self.flush_save() {
+            println!("{error}, could not write save file");
+        }
         if self.log_memory {
             self.log_memory();
             //self.gamepack.lock().unwrap().print(0, 16);
@@ -316,49 +407,139 @@ impl GameBoy {
         &self.cpu
     }*/
 
-    pub fn load_rom(&mut self, rom_path: std::path::PathBuf) {
+    /// Writes battery backed ram to the save file if it changed since the last flush
+    pub fn flush_save(&mut self) -> std::io::Result<()> {
+        let save_file = match &self.save_file {
+            Some(save_file) => save_file,
+            None => return Ok(()),
+        };
+        let mut memory = self.gamepack.lock().unwrap();
+        match memory.cartridge_mut() {
+            // the rtc keeps running so timer carts get saved every time
+            Some(cartridge) if cartridge.is_dirty() || cartridge.header.cartridge_type.timer => {
+                save_file.write(&cartridge.save_data())?;
+                cartridge.mark_saved();
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
 
-        let logo = [
-            0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
-            0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
-            0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E
-        ];
+    /// Adds an enabled Game Genie or GameShark code and returns its index
+    pub fn add_cheat(&self, code: &str, name: &str) -> Result<usize, CheatError> {
+        self.gamepack.lock().unwrap().cheats_mut().add(code, name)
+    }
 
+    /// Turns a cheat on or off while running, false when there's no cheat at `index`
+    pub fn set_cheat_enabled(&self, index: usize, enabled: bool) -> bool {
+        self.gamepack.lock().unwrap().cheats_mut().set_enabled(index, enabled)
+    }
 
-        let mut memory = self.gamepack.lock().unwrap();
-        match read(rom_path) {
-            Ok(buffer) => {
-                //rom banks
-                for i in 0..buffer.len() {
-                    memory.write(i as u16, buffer[i]);
-                } 
-
-                // external ram
-                if buffer.len() > 0xA000 {
-                    for i in 0xA000..0xBFFF {
-                        memory.write(i as u16, buffer[i]);
-                    }
-                }
-            }
-            Err(error) => match read(BOOT_ROM_PATH) {
-                Ok(buffer) => {
-                    println!("{error}, trying to recover");
-                    //load default boot rom
-                    for i in 0..min(buffer.len(), 0x10000) {
-                        memory.write(i as u16, buffer[i]);
-                    }
+    pub fn remove_cheat(&self, index: usize) -> Option<Cheat> {
+        self.gamepack.lock().unwrap().cheats_mut().remove(index)
+    }
 
-                    for i in 0..logo.len() {
-                        memory.write(0x0104 + i as u16, logo[i]);
-                    }
-                },
-                Err(error) => panic!("{error} boot rom error, file not found or incorrect file"),
-            },
+    pub fn cheats(&self) -> Vec<Cheat> {
+        self.gamepack.lock().unwrap().cheats().list().to_vec()
+    }
+
+    /// Whether the cartridge rumble motor is running
+    pub fn rumble(&self) -> bool {
+        self.gamepack.lock().unwrap().cartridge().is_some_and(|c| c.rumble())
+    }
+
+    /// Feeds light into the cartridge infrared receiver (HuC1, HuC3)
+    pub fn set_infrared(&self, light: bool) {
+        if let Some(cartridge) = self.gamepack.lock().unwrap().cartridge_mut() {
+            cartridge.set_infrared(light);
         }
+    }
 
+    /// Tilts carts with an accelerometer, holds until the next call or tilt key change
+    pub fn set_tilt(&self, tilt: Tilt) {
+        if let Some(cartridge) = self.gamepack.lock().unwrap().cartridge_mut() {
+            cartridge.set_tilt(tilt.x, tilt.y);
+        }
+    }
+
+    /// Picture source for the Pocket Camera, captures come out white without one
+    pub fn set_image_source(&self, source: Box<dyn ImageSource>) {
+        if let Some(cartridge) = self.gamepack.lock().unwrap().cartridge_mut() {
+            cartridge.set_image_source(source);
+        }
+    }
 
+    /// Cartridge events since the last call, events are handed to the event handler instead
+    /// while `run_emu` is running
+    pub fn take_cartridge_events(&mut self) -> Vec<CartridgeEvent> {
+        self.gamepack.lock().unwrap().cartridge_mut().map_or(Vec::new(), |c| c.take_events())
+    }
 
+    /// Called from the frame loop for every cartridge event (rumble, infrared, ...)
+    pub fn set_event_handler(&mut self, handler: impl FnMut(CartridgeEvent) + 'static) {
+        self.event_handler = Some(Box::new(handler));
+    }
 
+    /// Header of the loaded cartridge, None when running the boot rom on its own
+    pub fn header(&self) -> Option<&CartridgeHeader> {
+        self.header.as_ref()
+    }
+
+    /// Hashes of the loaded rom as dumped, before patches and without a GBX footer
+    pub fn identity(&self) -> Option<&RomIdentity> {
+        self.identity.as_ref()
+    }
+
+    /// Looks the loaded rom up in a DAT file
+    pub fn identify<'a>(&self, dat: &'a Dat) -> Option<&'a DatEntry> {
+        dat.identify(self.identity.as_ref()?)
+    }
+
+    pub fn load_rom(&mut self, rom_path: std::path::PathBuf) -> Result<(), LoadError> {
+        self.load_rom_entry(&rom_path, None)
+    }
+
+    /// Loads a rom file or archive, `entry` picks the archive member instead of the first rom
+    pub fn load_rom_entry(&mut self, rom_path: &Path, entry: Option<&str>) -> Result<(), LoadError> {
+        let rom = RomFile::open(rom_path, entry)?;
+        let patches = match self.patches.is_empty() {
+            true => patch::find_patch(&rom.path).into_iter().collect(),
+            false => self.patches.clone(),
+        };
+        // dat files list the original dumps, so hash before any patch goes on
+        let (unpatched, _) = GbxFooter::split(rom.data.clone()).map_err(LoadError::Cartridge)?;
+        let identity = RomIdentity::of(&unpatched);
+        let mut data = rom.data;
+        for path in patches {
+            data = patch::apply_file(&data, &path).map_err(|e| LoadError::Patch(path.clone(), e))?;
+            println!("applied patch {}", path.display());
+        }
+        let mut cartridge = Cartridge::new(data).map_err(LoadError::Cartridge)?;
+        for warning in cartridge.warnings() {
+            println!("warning: {warning}");
+        }
+        if cartridge.has_battery() {
+            // named after the rom even when it came out of an archive
+            let save_file = SaveFile::for_rom(&rom.path);
+            let data = save_file.load().map_err(|e| LoadError::Cartridge(CartridgeError::SaveFile(e)))?;
+            if let Some(data) = data {
+                cartridge.load_save_data(&data);
+            }
+            self.save_file = Some(save_file);
+        }
+        // game.cht next to game.gb, same as the save file
+        let cheat_path = rom.path.with_extension(CHEAT_EXTENSION);
+        let cheats = match cheat_path.is_file() {
+            true => Cheats::load(&cheat_path).map_err(LoadError::Cheat)?,
+            false => Cheats::new(),
+        };
+        self.header = Some(cartridge.header.clone());
+        self.identity = Some(identity);
+        let mut memory = self.gamepack.lock().unwrap();
+        memory.insert_cartridge(cartridge);
+        *memory.cheats_mut() = cheats;
+
+        Ok(())
     }
 
 }
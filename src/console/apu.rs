@@ -26,9 +26,27 @@ pub const NR34: u16 = 0xFF1F;
 
 pub const DUTY_CYCLES: [f32; 4] = [ 0.125, 0.25, 0.50, 0.75 ];
 
+// first and last sound register, including wave ram
+const APU_START: u16 = 0xFF10;
+const APU_END: u16 = 0xFF3F;
+
+// bits that always read back as 1, write-only and unused bits included
+// https://gbdev.io/pandocs/Audio_Registers.html
+const READ_MASKS: [u8; (APU_END - APU_START + 1) as usize] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // unused, NR21-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // unused, NR41-NR44
+    0x00, 0x00, 0x70,             // NR50-NR52
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, // wave ram
+    0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+];
+
 // 4 audio channels NRxy;
 #[derive(Debug, Default)]
 pub struct Apu {
+    registers: Vec<u8>,
     period1: u16,
     volume1: u8,
     phase: u16,
@@ -45,29 +63,50 @@ pub struct Apu {
 
 impl Apu {
     pub fn new() -> Apu {
-        Apu::default()
+        Apu {
+            registers: vec![0x00; (APU_END - APU_START + 1) as usize],
+            ..Apu::default()
+        }
+    }
+
+    /// Sound registers as seen from the bus
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            APU_START..=APU_END => self.register(addr) | READ_MASKS[(addr - APU_START) as usize],
+            _ => 0xFF,
+        }
+    }
+
+    fn register(&self, addr: u16) -> u8 {
+        self.registers[(addr - APU_START) as usize]
+    }
+
+    pub fn write(&mut self, addr: u16, byte: u8) {
+        if let APU_START..=APU_END = addr {
+            self.registers[(addr - APU_START) as usize] = byte;
+        }
     }
 
-    pub fn update(&mut self, memory: &mut Memory) {
+    pub fn update(&mut self) {
         self.sweeping = true;
         //channel 1
-        let nr10 = memory.read(NR10);
+        let nr10 = self.register(NR10);
         let pace = nr10 & 0b01110000 >> 4;
         let direction = nr10 & 0b00001000 >> 3;
         let individual_step = nr10 & 0b00000111;
         
-        let nr11 = memory.read(NR11);
+        let nr11 = self.register(NR11);
         let wave_duty = nr11 & 0b11000000 >> 6;
         let initial_length = nr11 & 0b00011111;
 
-        let nr12 = memory.read(NR12);
+        let nr12 = self.register(NR12);
         let initial_volume = nr12 & 0b11110000 >> 4;
         let env_dir = nr12 & 0b00001000 >> 3;
         let sweep_pace = nr12 & 0b00000111;
 
-        let period_low = memory.read(NR13);
+        let period_low = self.register(NR13);
 
-        let nr14 = memory.read(NR14);
+        let nr14 = self.register(NR14);
         let trigger = nr14 & 0b10000000 >> 7;
         let length_en = nr14 & 0b01000000 >> 6;
         let period_high = nr14 & 0b00000111;
@@ -94,7 +133,7 @@ impl Apu {
         }
 
         for i in 0..16 {
-            let x = memory.read(0xFF30 + i);
+            let x = self.register(0xFF30 + i);
             self.channel3.push(x & 0xF0 >> 4);
             self.channel3.push(x & 0x0F);
         }
@@ -0,0 +1,139 @@
+use std::fmt;
+use std::fs::read;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use super::regids::*;
+
+/*
+ *  Boot rom and the state it leaves behind
+ *  https://gbdev.io/pandocs/Power_Up_Sequence.html
+ */
+pub const DMG_BOOT_ROM_SIZE: usize = 0x100;
+pub const CGB_BOOT_ROM_SIZE: usize = 0x900;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Model {
+    #[default]
+    Dmg,
+    Mgb,
+    Sgb,
+    Sgb2,
+    Cgb,
+}
+
+impl FromStr for Model {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Model, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "dmg" => Ok(Model::Dmg),
+            "mgb" => Ok(Model::Mgb),
+            "sgb" => Ok(Model::Sgb),
+            "sgb2" => Ok(Model::Sgb2),
+            "cgb" => Ok(Model::Cgb),
+            _ => Err(format!("unknown model {s}, expected dmg, mgb, sgb, sgb2 or cgb")),
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Model::Dmg => "dmg",
+            Model::Mgb => "mgb",
+            Model::Sgb => "sgb",
+            Model::Sgb2 => "sgb2",
+            Model::Cgb => "cgb",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Reads a boot rom dump, only the DMG (256 byte) and CGB (2304 byte) sizes are accepted
+pub fn load_boot_rom(path: &Path) -> io::Result<Vec<u8>> {
+    let data = read(path)?;
+    check_boot_rom_size(data.len())?;
+    Ok(data)
+}
+
+/// Errors unless `len` is the size of a DMG (256 byte) or CGB (2304 byte) boot rom
+pub fn check_boot_rom_size(len: usize) -> io::Result<()> {
+    match len {
+        DMG_BOOT_ROM_SIZE | CGB_BOOT_ROM_SIZE => Ok(()),
+        len => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{len} bytes is not the size of a boot rom"),
+        )),
+    }
+}
+
+/// A F B C D E H L as the boot rom of `model` leaves them, the DMG flags depend on the cartridge
+/// header checksum
+pub fn post_boot_registers(model: Model, header_checksum: u8) -> [u8; 8] {
+    let dmg_f = if header_checksum == 0 { 0x80 } else { 0xB0 };
+    match model {
+        Model::Dmg => [0x01, dmg_f, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+        Model::Mgb => [0xFF, dmg_f, 0x00, 0x13, 0x00, 0xD8, 0x01, 0x4D],
+        Model::Sgb => [0x01, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+        Model::Sgb2 => [0xFF, 0x00, 0x00, 0x14, 0x00, 0x00, 0xC0, 0x60],
+        Model::Cgb => [0x11, 0x80, 0x00, 0x00, 0xFF, 0x56, 0x00, 0x0D],
+    }
+}
+
+/// DIV after the boot rom hands over, it counts while the boot rom runs
+pub fn post_boot_div(model: Model) -> u8 {
+    match model {
+        Model::Dmg | Model::Mgb => 0xAB,
+        Model::Sgb | Model::Sgb2 => 0xD8,
+        Model::Cgb => 0x1E,
+    }
+}
+
+/// Writable I/O registers as the boot rom of `model` leaves them, DIV and DMA aren't included
+pub fn post_boot_io(model: Model) -> Vec<(u16, u8)> {
+    let nr52 = match model {
+        Model::Sgb | Model::Sgb2 => 0xF0,
+        _ => 0xF1,
+    };
+    vec![
+        (0xFF00, 0xCF),
+        (0xFF01, 0x00),
+        (0xFF02, 0x7E),
+        (TIMA, 0x00),
+        (TMA, 0x00),
+        (TAC, 0xF8),
+        (IF, 0xE1),
+        (0xFF10, 0x80),
+        (0xFF11, 0xBF),
+        (0xFF12, 0xF3),
+        (0xFF13, 0xFF),
+        (0xFF14, 0xBF),
+        (0xFF16, 0x3F),
+        (0xFF17, 0x00),
+        (0xFF18, 0xFF),
+        (0xFF19, 0xBF),
+        (0xFF1A, 0x7F),
+        (0xFF1B, 0xFF),
+        (0xFF1C, 0x9F),
+        (0xFF1D, 0xFF),
+        (0xFF1E, 0xBF),
+        (0xFF20, 0xFF),
+        (0xFF21, 0x00),
+        (0xFF22, 0x00),
+        (0xFF23, 0xBF),
+        (0xFF24, 0x77),
+        (0xFF25, 0xF3),
+        (0xFF26, nr52),
+        (0xFF40, 0x91),
+        (0xFF41, 0x85),
+        (0xFF42, 0x00),
+        (0xFF43, 0x00),
+        (0xFF45, 0x00),
+        (0xFF47, 0xFC),
+        (0xFF4A, 0x00),
+        (0xFF4B, 0x00),
+        (IE, 0x00),
+    ]
+}
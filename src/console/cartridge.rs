@@ -0,0 +1,501 @@
+use core::fmt;
+
+use crate::mbc::*;
+use crate::mbc::rtc::*;
+use crate::mbc::sensor::ImageSource;
+use crate::gbx::GbxFooter;
+
+/*
+ *  Cartridge header locations
+ *  https://gbdev.io/pandocs/The_Cartridge_Header.html
+ */
+pub const HEADER_START: usize = 0x0100;
+pub const HEADER_END: usize = 0x0150;
+
+const LOGO_START: usize = 0x0104;
+const TITLE_START: usize = 0x0134;
+const MANUFACTURER_START: usize = 0x013F;
+const CGB_FLAG: usize = 0x0143;
+const NEW_LICENSEE: usize = 0x0144;
+const SGB_FLAG: usize = 0x0146;
+const CARTRIDGE_TYPE: usize = 0x0147;
+const ROM_SIZE: usize = 0x0148;
+const RAM_SIZE: usize = 0x0149;
+const DESTINATION: usize = 0x014A;
+const OLD_LICENSEE: usize = 0x014B;
+const VERSION: usize = 0x014C;
+pub const HEADER_CHECKSUM: usize = 0x014D;
+const GLOBAL_CHECKSUM: usize = 0x014E;
+
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+/// 512 banks on MBC5, the largest of any cartridge
+pub const MAX_ROM_SIZE: usize = 512 * ROM_BANK_SIZE;
+/// 16 banks on MBC5, the largest of any cartridge
+pub const MAX_RAM_SIZE: usize = 16 * RAM_BANK_SIZE;
+
+pub const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E
+];
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    TooSmall(usize),
+    HeaderChecksum { expected: u8, computed: u8 },
+    UnknownCartridgeType(u8),
+    UnknownRomSize(u8),
+    UnknownRamSize(u8),
+    UnsupportedMapper(Mapper),
+    SaveFile(std::io::Error),
+    GbxVersion { major: u32, minor: u32 },
+    GbxFooterSize(usize),
+    UnknownGbxMapper(String),
+    /// Rom or ram size in the GBX footer larger than any cartridge
+    GbxRomSize(usize),
+    GbxRamSize(usize),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::TooSmall(len) => {
+                write!(f, "rom is {len:#X} bytes, too small to contain a cartridge header")
+            }
+            CartridgeError::HeaderChecksum { expected, computed } => {
+                write!(f, "header checksum mismatch, expected {expected:#04X} but computed {computed:#04X}")
+            }
+            CartridgeError::UnknownCartridgeType(code) => write!(f, "unknown cartridge type {code:#04X}"),
+            CartridgeError::UnknownRomSize(code) => write!(f, "unknown rom size code {code:#04X}"),
+            CartridgeError::UnknownRamSize(code) => write!(f, "unknown ram size code {code:#04X}"),
+            CartridgeError::UnsupportedMapper(mapper) => write!(f, "{mapper:?} cartridges are not supported"),
+            CartridgeError::SaveFile(error) => write!(f, "could not read save file, {error}"),
+            CartridgeError::GbxVersion { major, minor } => write!(f, "unsupported GBX footer version {major}.{minor}"),
+            CartridgeError::GbxFooterSize(size) => write!(f, "GBX footer size {size:#X} is invalid"),
+            CartridgeError::UnknownGbxMapper(id) => write!(f, "unknown GBX mapper {id:?}"),
+            CartridgeError::GbxRomSize(size) => write!(f, "GBX rom size {size:#X} is larger than any cartridge"),
+            CartridgeError::GbxRamSize(size) => write!(f, "GBX ram size {size:#X} is larger than any cartridge"),
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {}
+
+/// Problems with the header that real hardware does not care about
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderWarning {
+    LogoMismatch,
+    GlobalChecksum { expected: u16, computed: u16 },
+    RomSizeMismatch { header: usize, actual: usize },
+}
+
+impl fmt::Display for HeaderWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeaderWarning::LogoMismatch => write!(f, "nintendo logo does not match"),
+            HeaderWarning::GlobalChecksum { expected, computed } => {
+                write!(f, "global checksum mismatch, expected {expected:#06X} but computed {computed:#06X}")
+            }
+            HeaderWarning::RomSizeMismatch { header, actual } => {
+                write!(f, "header says rom is {header:#X} bytes but file is {actual:#X} bytes")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mapper {
+    RomOnly,
+    Mbc1,
+    Mbc2,
+    Mmm01,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    PocketCamera,
+    Tama5,
+    HuC3,
+    HuC1,
+}
+
+/// Decoded cartridge type byte (0x0147)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CartridgeType {
+    pub code: u8,
+    pub mapper: Mapper,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+}
+
+impl CartridgeType {
+    pub fn from_code(code: u8) -> Option<CartridgeType> {
+        use Mapper::*;
+        //(mapper, ram, battery, timer, rumble)
+        let (mapper, ram, battery, timer, rumble) = match code {
+            0x00 => (RomOnly, false, false, false, false),
+            0x01 => (Mbc1, false, false, false, false),
+            0x02 => (Mbc1, true, false, false, false),
+            0x03 => (Mbc1, true, true, false, false),
+            0x05 => (Mbc2, false, false, false, false),
+            0x06 => (Mbc2, false, true, false, false),
+            0x08 => (RomOnly, true, false, false, false),
+            0x09 => (RomOnly, true, true, false, false),
+            0x0B => (Mmm01, false, false, false, false),
+            0x0C => (Mmm01, true, false, false, false),
+            0x0D => (Mmm01, true, true, false, false),
+            0x0F => (Mbc3, false, true, true, false),
+            0x10 => (Mbc3, true, true, true, false),
+            0x11 => (Mbc3, false, false, false, false),
+            0x12 => (Mbc3, true, false, false, false),
+            0x13 => (Mbc3, true, true, false, false),
+            0x19 => (Mbc5, false, false, false, false),
+            0x1A => (Mbc5, true, false, false, false),
+            0x1B => (Mbc5, true, true, false, false),
+            0x1C => (Mbc5, false, false, false, true),
+            0x1D => (Mbc5, true, false, false, true),
+            0x1E => (Mbc5, true, true, false, true),
+            0x20 => (Mbc6, true, true, false, false),
+            0x22 => (Mbc7, true, true, false, true),
+            0xFC => (PocketCamera, true, true, false, false),
+            0xFD => (Tama5, true, true, true, false),
+            0xFE => (HuC3, true, true, true, false),
+            0xFF => (HuC1, true, true, false, false),
+            _ => return None,
+        };
+        Some(CartridgeType { code, mapper, ram, battery, timer, rumble })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CgbSupport {
+    None,
+    Enhanced,
+    Only,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    Japan,
+    Overseas,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub manufacturer_code: Option<String>,
+    pub cgb: CgbSupport,
+    pub sgb: bool,
+    pub licensee: u16,
+    pub cartridge_type: CartridgeType,
+    pub rom_size_code: u8,
+    pub ram_size_code: u8,
+    pub destination: Destination,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+    /// Footer the cartridge type and sizes came from instead of the header bytes
+    pub gbx: Option<GbxFooter>,
+}
+
+impl CartridgeHeader {
+    /// Parses the header at 0x0100-0x014F and checks the header checksum
+    pub fn parse(rom: &[u8]) -> Result<CartridgeHeader, CartridgeError> {
+        CartridgeHeader::parse_with_footer(rom, None)
+    }
+
+    /// Like `parse`, but a GBX footer overrides the cartridge type, rom and ram size bytes
+    pub fn parse_with_footer(rom: &[u8], gbx: Option<GbxFooter>) -> Result<CartridgeHeader, CartridgeError> {
+        if rom.len() < HEADER_END {
+            return Err(CartridgeError::TooSmall(rom.len()));
+        }
+
+        let computed = CartridgeHeader::compute_header_checksum(rom);
+        let expected = rom[HEADER_CHECKSUM];
+        if computed != expected {
+            return Err(CartridgeError::HeaderChecksum { expected, computed });
+        }
+
+        let cgb = match rom[CGB_FLAG] {
+            0x80 => CgbSupport::Enhanced,
+            0xC0 => CgbSupport::Only,
+            _ => CgbSupport::None,
+        };
+
+        // newer carts took the last bytes of the title for the manufacturer code and cgb flag
+        let (title_end, manufacturer_code) = match cgb {
+            CgbSupport::None => (CGB_FLAG + 1, None),
+            _ => {
+                let code = &rom[MANUFACTURER_START..CGB_FLAG];
+                let code = if code.iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+                    Some(String::from_utf8_lossy(code).into_owned())
+                } else {
+                    None
+                };
+                (if code.is_some() { MANUFACTURER_START } else { CGB_FLAG }, code)
+            }
+        };
+        let title = rom[TITLE_START..title_end]
+            .iter()
+            .take_while(|c| **c != 0)
+            .map(|c| *c as char)
+            .collect::<String>()
+            .trim_end()
+            .to_string();
+
+        let licensee = match rom[OLD_LICENSEE] {
+            0x33 => u16::from_be_bytes([rom[NEW_LICENSEE], rom[NEW_LICENSEE + 1]]),
+            code => code as u16,
+        };
+
+        let type_code = rom[CARTRIDGE_TYPE];
+        let rom_size_code = rom[ROM_SIZE];
+        let ram_size_code = rom[RAM_SIZE];
+        // the footer is there because these bytes can't be trusted
+        let cartridge_type = match &gbx {
+            Some(gbx) => CartridgeType {
+                code: type_code,
+                mapper: gbx.mapper,
+                ram: gbx.ram_size > 0,
+                battery: gbx.battery,
+                timer: gbx.timer,
+                rumble: gbx.rumble,
+            },
+            None => {
+                if rom_size_from_code(rom_size_code).is_none() {
+                    return Err(CartridgeError::UnknownRomSize(rom_size_code));
+                }
+                if ram_size_from_code(ram_size_code).is_none() {
+                    return Err(CartridgeError::UnknownRamSize(ram_size_code));
+                }
+                CartridgeType::from_code(type_code).ok_or(CartridgeError::UnknownCartridgeType(type_code))?
+            }
+        };
+
+        Ok(CartridgeHeader {
+            title,
+            manufacturer_code,
+            cgb,
+            sgb: rom[SGB_FLAG] == 0x03,
+            licensee,
+            cartridge_type,
+            rom_size_code,
+            ram_size_code,
+            destination: if rom[DESTINATION] == 0 { Destination::Japan } else { Destination::Overseas },
+            version: rom[VERSION],
+            header_checksum: expected,
+            global_checksum: u16::from_be_bytes([rom[GLOBAL_CHECKSUM], rom[GLOBAL_CHECKSUM + 1]]),
+            gbx,
+        })
+    }
+
+    pub fn compute_header_checksum(rom: &[u8]) -> u8 {
+        rom[TITLE_START..HEADER_CHECKSUM]
+            .iter()
+            .fold(0u8, |x, byte| x.wrapping_sub(*byte).wrapping_sub(1))
+    }
+
+    /// Sum of every byte in the rom except the two checksum bytes
+    pub fn compute_global_checksum(rom: &[u8]) -> u16 {
+        rom.iter()
+            .enumerate()
+            .filter(|(i, _)| *i != GLOBAL_CHECKSUM && *i != GLOBAL_CHECKSUM + 1)
+            .fold(0u16, |sum, (_, byte)| sum.wrapping_add(*byte as u16))
+    }
+
+    pub fn rom_size(&self) -> usize {
+        match &self.gbx {
+            Some(gbx) => gbx.rom_size,
+            None => rom_size_from_code(self.rom_size_code).unwrap_or(0),
+        }
+    }
+
+    pub fn ram_size(&self) -> usize {
+        match &self.gbx {
+            Some(gbx) => gbx.ram_size,
+            None => ram_size_from_code(self.ram_size_code).unwrap_or(0),
+        }
+    }
+
+    /// Checks the parts of the header that the boot rom ignores
+    pub fn warnings(&self, rom: &[u8]) -> Vec<HeaderWarning> {
+        let mut warnings = Vec::new();
+        if rom[LOGO_START..LOGO_START + NINTENDO_LOGO.len()] != NINTENDO_LOGO {
+            warnings.push(HeaderWarning::LogoMismatch);
+        }
+        let computed = CartridgeHeader::compute_global_checksum(rom);
+        if computed != self.global_checksum {
+            warnings.push(HeaderWarning::GlobalChecksum { expected: self.global_checksum, computed });
+        }
+        if rom.len() != self.rom_size() {
+            warnings.push(HeaderWarning::RomSizeMismatch { header: self.rom_size(), actual: rom.len() });
+        }
+        warnings
+    }
+}
+
+/// rom size in bytes
+pub fn rom_size_from_code(code: u8) -> Option<usize> {
+    match code {
+        0x00..=0x08 => Some((32 * 1024) << code),
+        0x52 => Some(72 * ROM_BANK_SIZE),
+        0x53 => Some(80 * ROM_BANK_SIZE),
+        0x54 => Some(96 * ROM_BANK_SIZE),
+        _ => None,
+    }
+}
+
+/// ram size in bytes
+pub fn ram_size_from_code(code: u8) -> Option<usize> {
+    match code {
+        0x00 => Some(0),
+        0x01 => Some(2 * 1024),
+        0x02 => Some(RAM_BANK_SIZE),
+        0x03 => Some(4 * RAM_BANK_SIZE),
+        0x04 => Some(16 * RAM_BANK_SIZE),
+        0x05 => Some(8 * RAM_BANK_SIZE),
+        _ => None,
+    }
+}
+
+#[derive(Debug)]
+pub struct Cartridge {
+    pub header: CartridgeHeader,
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    // ram written since the last save
+    dirty: bool,
+    mbc: Box<dyn Mbc>,
+}
+
+impl Cartridge {
+    /// Builds a cartridge from a rom file, a GBX footer picks the hardware over the header
+    pub fn new(rom: Vec<u8>) -> Result<Cartridge, CartridgeError> {
+        let (rom, gbx) = GbxFooter::split(rom)?;
+        let header = CartridgeHeader::parse_with_footer(&rom, gbx)?;
+        let multicart = match &header.gbx {
+            Some(gbx) => gbx.multicart,
+            None => Mbc1::is_multicart(&rom),
+        };
+        let mbc: Box<dyn Mbc> = match header.cartridge_type.mapper {
+            Mapper::RomOnly => Box::new(RomOnly),
+            Mapper::Mbc1 => Box::new(Mbc1::new(multicart)),
+            Mapper::Mbc2 => Box::new(Mbc2::new()),
+            Mapper::Mbc3 => {
+                let rtc = match header.cartridge_type.timer {
+                    true => Some(Rtc::new(Box::new(SystemClock))),
+                    false => None,
+                };
+                Box::new(Mbc3::new(rtc))
+            }
+            Mapper::Mbc5 => Box::new(Mbc5::new(header.cartridge_type.rumble)),
+            Mapper::Mbc7 => Box::new(Mbc7::new()),
+            Mapper::PocketCamera => Box::new(PocketCamera::new()),
+            Mapper::HuC1 => Box::new(HuC1::new()),
+            Mapper::HuC3 => Box::new(HuC3::new(Box::new(SystemClock))),
+            mapper => return Err(CartridgeError::UnsupportedMapper(mapper)),
+        };
+        Ok(Cartridge::with_mbc(header, rom, mbc))
+    }
+
+    /// Builds a cartridge around an already constructed mapper
+    pub fn with_mbc(header: CartridgeHeader, rom: Vec<u8>, mbc: Box<dyn Mbc>) -> Cartridge {
+        let ram = vec![mbc.ram_fill(); mbc.ram_size().unwrap_or(header.ram_size())];
+        Cartridge { header, rom, ram, dirty: false, mbc }
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    pub fn warnings(&self) -> Vec<HeaderWarning> {
+        self.header.warnings(&self.rom)
+    }
+
+    pub fn read_rom(&self, addr: u16) -> u8 {
+        self.mbc.read_rom(&self.rom, addr)
+    }
+
+    pub fn write_rom(&mut self, addr: u16, byte: u8) {
+        self.mbc.write_rom(addr, byte);
+    }
+
+    pub fn read_ram(&self, addr: u16) -> u8 {
+        self.mbc.read_ram(&self.ram, addr)
+    }
+
+    pub fn write_ram(&mut self, addr: u16, byte: u8) {
+        self.mbc.write_ram(&mut self.ram, addr, byte);
+        self.dirty = true;
+    }
+
+    /// Writes straight into a ram bank, whether it's mapped or not
+    pub fn write_ram_bank(&mut self, bank: usize, addr: u16, byte: u8) {
+        if !self.ram.is_empty() {
+            let index = ram_bank_index(&self.ram, bank, addr);
+            self.ram[index] = byte;
+            self.dirty = true;
+        }
+    }
+
+    pub fn has_battery(&self) -> bool {
+        self.header.cartridge_type.battery
+    }
+
+    /// Whether ram was written since the last `mark_saved`
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Call once `save_data` made it to disk, a failed write stays dirty to be retried
+    pub fn mark_saved(&mut self) {
+        self.dirty = false;
+    }
+
+    /// Contents of the .sav file, the raw ram followed by any mapper state
+    pub fn save_data(&self) -> Vec<u8> {
+        let mut data = self.ram.clone();
+        data.extend(self.mbc.save_extra());
+        data
+    }
+
+    pub fn load_save_data(&mut self, data: &[u8]) {
+        let len = self.ram.len().min(data.len());
+        self.ram[..len].copy_from_slice(&data[..len]);
+        self.mbc.load_extra(&data[len..]);
+    }
+
+    pub fn rumble(&self) -> bool {
+        self.mbc.rumble()
+    }
+
+    pub fn take_events(&mut self) -> Vec<CartridgeEvent> {
+        self.mbc.take_events()
+    }
+
+    /// Whether the infrared receiver sees light, for carts with an IR port
+    pub fn set_infrared(&mut self, light: bool) {
+        self.mbc.set_infrared(light);
+    }
+
+    /// Tilt in g for carts with an accelerometer (MBC7)
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.mbc.set_tilt(x, y);
+    }
+
+    /// Where the Pocket Camera sensor gets its pictures from
+    pub fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.mbc.set_image_source(source);
+    }
+
+    pub fn tick(&mut self) {
+        self.mbc.tick();
+    }
+}
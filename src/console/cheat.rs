@@ -0,0 +1,220 @@
+use core::fmt;
+use std::fs::read_to_string;
+use std::io;
+use std::path::Path;
+
+/*
+ *  Cheat codes
+ *  Game Genie codes replace rom reads, GameShark codes write ram once a frame
+ *  https://gbdev.io/pandocs/Shark_Cheats.html
+ */
+/// Cheat files sit next to the rom, game.cht for game.gb
+pub const CHEAT_EXTENSION: &str = "cht";
+
+const GENIE_SHORT_LEN: usize = 6;
+const GENIE_LONG_LEN: usize = 9;
+const SHARK_LEN: usize = 8;
+
+// GameShark code types, the low bits of the banked ones pick the bank
+const SHARK_CURRENT: [u8; 2] = [0x00, 0x01];
+const SHARK_RAM_BANK: u8 = 0x80;
+const SHARK_WRAM_BANK: u8 = 0x90;
+// GameShark codes only write ram, below this is the rom and its mapper registers
+const SHARK_MIN_ADDRESS: u16 = 0x8000;
+
+#[derive(Debug)]
+pub enum CheatError {
+    Io(io::Error),
+    /// Not a Game Genie or GameShark code, with the line it was on in a cheat file
+    InvalidCode(String, Option<usize>),
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheatError::Io(error) => write!(f, "could not read cheat file, {error}"),
+            CheatError::InvalidCode(code, None) => write!(f, "invalid cheat code {code}"),
+            CheatError::InvalidCode(code, Some(line)) => write!(f, "invalid cheat code {code} on line {line}"),
+        }
+    }
+}
+
+impl std::error::Error for CheatError {}
+
+/// Which ram a GameShark code writes to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SharkBank {
+    /// Whatever is mapped at the address
+    Current,
+    /// Cartridge ram bank, mapped or not
+    Ram(u8),
+    /// Work ram bank at 0xD000-0xDFFF
+    Wram(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatKind {
+    /// Reads of `address` return `value`, only when the rom holds `compare` if given
+    GameGenie { address: u16, value: u8, compare: Option<u8> },
+    /// `value` is written to `address` every frame
+    GameShark { bank: SharkBank, address: u16, value: u8 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub code: String,
+    pub name: String,
+    pub enabled: bool,
+    pub kind: CheatKind,
+}
+
+impl Cheat {
+    /// Parses a Game Genie (ABC-DEF or ABC-DEF-GHI) or GameShark (TTVVLLHH) code
+    pub fn new(code: &str, name: &str) -> Result<Cheat, CheatError> {
+        let digits = code
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<Vec<u8>>>();
+        let kind = match digits {
+            Some(digits) if code.contains('-') || digits.len() != SHARK_LEN => parse_genie(&digits),
+            Some(digits) => parse_shark(&digits),
+            None => None,
+        };
+        match kind {
+            Some(kind) => Ok(Cheat {
+                code: code.to_uppercase(),
+                name: name.to_string(),
+                enabled: true,
+                kind,
+            }),
+            None => Err(CheatError::InvalidCode(code.to_string(), None)),
+        }
+    }
+}
+
+fn parse_genie(digits: &[u8]) -> Option<CheatKind> {
+    if digits.len() != GENIE_SHORT_LEN && digits.len() != GENIE_LONG_LEN {
+        return None;
+    }
+    let value = digits[0] << 4 | digits[1];
+    let address = ((digits[5] ^ 0xF) as u16) << 12
+        | (digits[2] as u16) << 8
+        | (digits[3] as u16) << 4
+        | digits[4] as u16;
+    if address > 0x7FFF {
+        return None;
+    }
+    // digit H is only there to make the code look random
+    let compare = match digits.len() {
+        GENIE_LONG_LEN => Some((digits[6] << 4 | digits[8]).rotate_right(2) ^ 0xBA),
+        _ => None,
+    };
+    Some(CheatKind::GameGenie { address, value, compare })
+}
+
+fn parse_shark(digits: &[u8]) -> Option<CheatKind> {
+    let byte = |i: usize| digits[i] << 4 | digits[i + 1];
+    let code_type = byte(0);
+    let bank = match code_type {
+        t if SHARK_CURRENT.contains(&t) => SharkBank::Current,
+        t if t & 0xF0 == SHARK_RAM_BANK => SharkBank::Ram(t & 0x0F),
+        t if t & 0xF8 == SHARK_WRAM_BANK => SharkBank::Wram(t & 0x07),
+        _ => return None,
+    };
+    let address = u16::from_le_bytes([byte(4), byte(6)]);
+    // writes to rom would switch banks on the mapper every frame
+    if address < SHARK_MIN_ADDRESS {
+        return None;
+    }
+    Some(CheatKind::GameShark { bank, address, value: byte(2) })
+}
+
+/// Cheats for the running rom, in the order they were added
+#[derive(Debug, Default, Clone)]
+pub struct Cheats {
+    list: Vec<Cheat>,
+}
+
+impl Cheats {
+    pub fn new() -> Cheats {
+        Cheats { list: Vec::new() }
+    }
+
+    /// Reads a cheat file, one code per line followed by an optional name
+    ///
+    /// Lines starting with # are comments, codes starting with - are loaded disabled.
+    pub fn load(path: &Path) -> Result<Cheats, CheatError> {
+        let text = read_to_string(path).map_err(CheatError::Io)?;
+        Cheats::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Cheats, CheatError> {
+        let mut cheats = Cheats::new();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (code, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let (code, enabled) = match code.strip_prefix('-') {
+                Some(code) => (code, false),
+                None => (code, true),
+            };
+            let mut cheat = Cheat::new(code, name.trim())
+                .map_err(|_| CheatError::InvalidCode(code.to_string(), Some(i + 1)))?;
+            cheat.enabled = enabled;
+            cheats.list.push(cheat);
+        }
+        Ok(cheats)
+    }
+
+    /// Adds an enabled cheat and returns its index
+    pub fn add(&mut self, code: &str, name: &str) -> Result<usize, CheatError> {
+        self.list.push(Cheat::new(code, name)?);
+        Ok(self.list.len() - 1)
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Cheat> {
+        (index < self.list.len()).then(|| self.list.remove(index))
+    }
+
+    /// Returns false when there's no cheat at `index`
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) -> bool {
+        match self.list.get_mut(index) {
+            Some(cheat) => {
+                cheat.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn list(&self) -> &[Cheat] {
+        &self.list
+    }
+
+    /// The byte a rom read returns once Game Genie codes are applied
+    pub fn genie_read(&self, addr: u16, byte: u8) -> u8 {
+        for cheat in self.list.iter().filter(|cheat| cheat.enabled) {
+            if let CheatKind::GameGenie { address, value, compare } = cheat.kind {
+                if address == addr && compare.is_none_or(|compare| compare == byte) {
+                    return value;
+                }
+            }
+        }
+        byte
+    }
+
+    /// Enabled GameShark writes as (bank, address, value)
+    pub fn shark_writes(&self) -> Vec<(SharkBank, u16, u8)> {
+        self.list
+            .iter()
+            .filter(|cheat| cheat.enabled)
+            .filter_map(|cheat| match cheat.kind {
+                CheatKind::GameShark { bank, address, value } => Some((bank, address, value)),
+                _ => None,
+            })
+            .collect()
+    }
+}
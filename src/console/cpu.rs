@@ -6,11 +6,56 @@ use crate::console::regids::*;
 use crate::cpu::identifiers::*;
 use crate::cpu::instruction::*;
 use crate::memory::*;
+use crate::boot::*;
+use crate::power::{PowerOnState, Region};
+use crate::cartridge::HEADER_CHECKSUM;
 
 use Instruction::*;
 
 use std::fmt;
 
+// VBlank's handler, the others follow 8 bytes apart
+const INTERRUPT_VECTORS: u16 = 0x0040;
+
+/// Every M-cycle the cpu takes ticks the rest of the machine this many dots,
+/// half as many in CGB double speed
+pub const DOTS_PER_M_CYCLE: usize = 4;
+
+// how long a CGB speed switch keeps the cpu idle
+const SPEED_SWITCH_M_CYCLES: usize = 2050;
+
+// opcodes with no instruction, the cpu locks up on them
+const UNDEFINED_OPCODES: [u8; 11] = [0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpuError {
+    /// One of the undefined opcodes, at the address it was fetched from
+    IllegalOpcode { opcode: u8, pc: u16 },
+    /// Decoded to something execute has no case for
+    Unhandled { instr: Instruction, pc: u16 },
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::IllegalOpcode { opcode, pc } => write!(f, "illegal opcode {opcode:#04X} at {pc:#06X}"),
+            CpuError::Unhandled { instr, pc } => write!(f, "unhandled instruction {instr:?} at {pc:#06X}"),
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+/// What the cpu does after a `CpuError`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LockupAction {
+    /// Stop executing until reset like hardware, the rest of the machine keeps running
+    #[default]
+    Lock,
+    /// Leave PC on the faulting instruction so a debugger can look at it,
+    /// running again hits the same error
+    Break,
+}
 
 // the cpu
 pub struct SharpSM83 {
@@ -26,15 +71,24 @@ pub struct SharpSM83 {
     //8-bit flag / 0-3 grounded to 0, 4 carry flag C, 5, half-carry H, 6 negative N, 7 zero Z
     f: u8,
     ime: u8,
+    // EI enables interrupts after the instruction following it
+    ime_delay: bool,
 
     //16-bit special purpose
     pub pc: u16,
     sp: u16,
-    boot_rom: Memory,
     m_cycles: usize,
-    pub rom_control: bool,
     pub stop: bool,
     pub halt: bool,
+    // HALT with IME=0 and an interrupt pending doesn't halt, the next fetch
+    // fails to increment PC and the byte after HALT is read twice
+    halt_bug: bool,
+    locked: bool,
+    lockup_action: LockupAction,
+    // reported by the next `run`
+    fault: Option<CpuError>,
+    // where the running instruction was fetched from
+    instr_pc: u16,
     last_instr: Instruction,
     instructions_executed: usize,
 }
@@ -43,11 +97,18 @@ impl SharpSM83 {
     pub fn is_interruptible(&self) -> bool {
         self.ime == 1
     }
+
+    /// Locked up on an illegal opcode, only `reset` gets it going again
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn set_lockup_action(&mut self, action: LockupAction) {
+        self.lockup_action = action;
+    }
     
     pub fn new_test() -> SharpSM83 {
-        let mut cpu = SharpSM83::new();
-        cpu.rom_control = true;
-        cpu
+        SharpSM83::new()
     }
 
     pub fn new() -> SharpSM83 {
@@ -63,6 +124,7 @@ impl SharpSM83 {
             f: 0x00, // Z N H C 0 0 0 0
 
             ime: 0,
+            ime_delay: false,
 
             pc: 0x0000,
             sp: 0x0000,
@@ -70,40 +132,84 @@ impl SharpSM83 {
             m_cycles: 0,
             stop: false,
             halt: false,
-            boot_rom: Memory::from_file(0x100, BOOT_ROM_PATH),
-            rom_control: false,
+            halt_bug: false,
+            locked: false,
+            lockup_action: LockupAction::default(),
+            fault: None,
+            instr_pc: 0x0000,
 
             instructions_executed: 0,
         }
     }
     
-    pub fn update(&mut self, memory: &mut Memory) {
-        //println!("{}", self.rom_control);
-        if self.halt && (self.read(IE, memory) & self.read(IF, memory) != 0) {
-            self.halt = false;
-        }
+    /// Registers before the boot rom sets them, PC always starts at 0
+    pub fn power_on(&mut self, state: &PowerOnState) {
+        let r = state.contents(Region::Registers, 10);
+        (self.a, self.f, self.b, self.c) = (r[0], r[1] & 0xF0, r[2], r[3]);
+        (self.d, self.e, self.h, self.l) = (r[4], r[5], r[6], r[7]);
+        self.sp = u16::from_le_bytes([r[8], r[9]]);
+    }
+
+    /// Starts at 0x0100 with the registers the boot rom of `model` leaves behind
+    pub fn skip_boot(&mut self, model: Model, memory: &Memory) {
+        let header_checksum = memory.read(HEADER_CHECKSUM as u16);
+        let [a, f, b, c, d, e, h, l] = post_boot_registers(model, header_checksum);
+        (self.a, self.f, self.b, self.c) = (a, f, b, c);
+        (self.d, self.e, self.h, self.l) = (d, e, h, l);
+        self.sp = 0xFFFE;
+        self.pc = 0x0100;
+    }
 
-        if self.stop && self.read(0xFF00, memory) & 0xF != 0x0 {
+    pub fn update(&mut self, memory: &mut Memory) {
+        // buttons are active low, any selected line going low wakes it
+        if self.stop && memory.read(0xFF00) & 0xF != 0xF {
             self.stop = false;
+            memory.wake();
         }
     }
 
-    //returns the number of clock cycles for the instruction
-    pub fn run(&mut self, memory: &mut Memory) -> usize {
+    /// Runs one instruction, or one M-cycle while halted, stopped or locked up,
+    /// and returns the M-cycles it took. The rest of the machine has already
+    /// been ticked through them by then
+    ///
+    /// Errors once when the instruction locks the cpu up, or every time it's
+    /// run with `LockupAction::Break`
+    pub fn run(&mut self, memory: &mut Memory) -> Result<usize, CpuError> {
+        self.run_with(memory, Instruction::decode)
+    }
+
+    /// `run` with another decoder for unprefixed opcodes, for benchmarking them against each other
+    pub fn run_with(&mut self, memory: &mut Memory, decode: fn(u8) -> Instruction) -> Result<usize, CpuError> {
         let last_m_cycles = self.m_cycles;
+        if self.locked {
+            self.idle(memory);
+            return Ok(self.m_cycles - last_m_cycles);
+        }
+        // a pending interrupt ends HALT even with IME=0, it just isn't serviced
+        if self.halt && interrupt_pending(memory) {
+            self.halt = false;
+        }
         if !self.stop {
             if self.ime == 1 {
-                if self.handle_interrupt(memory) { self.m_cycles += 4 };
+                self.handle_interrupt(memory);
             }
             if !self.halt {
+                self.instr_pc = self.pc;
                 let opcode = self.fetch(memory);
-                let instr = Instruction::decode(opcode);
+                let instr = decode(opcode);
                 self.last_instr = instr;
                 self.execute(instr, memory);
                 self.instructions_executed += 1;
             }
         }
-        self.m_cycles - last_m_cycles
+        if self.m_cycles == last_m_cycles {
+            // halted, the clock keeps running
+            self.idle(memory);
+        }
+        match self.fault.take() {
+            Some(error) => Err(error),
+            None => Ok(self.m_cycles - last_m_cycles),
+        }
     }
 
 
@@ -112,6 +218,7 @@ impl SharpSM83 {
     }
 
     pub fn raw_run(&mut self, memory: &mut Memory) {
+        self.instr_pc = self.pc;
         let opcode = self.fetch(memory);
         let instr = Instruction::decode(opcode);
         //eprintln!("{:?}", instr);
@@ -119,43 +226,63 @@ impl SharpSM83 {
         //self.print_info();
     }
 
-    fn fetch(&mut self, memory: &Memory) -> u8 {
-        self.m_cycles += 1;
-        //this should either be 0xff or 0x100
-        if self.pc == 0xFF && !self.rom_control {
-            self.rom_control = true;
-            self.print();
-            memory.print(0xFF00, 0xFFFF);
-        }
+    fn fetch(&mut self, memory: &mut Memory) -> u8 {
         // ---- get instruction from memory  ----
         let opcode = self.read(self.pc, memory);
         //eprintln!("fetched {0:#04X} at pc: {1:#04X}", opcode, self.pc);
-        self.pc = self.pc.overflowing_add(1).0;
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.pc = self.pc.overflowing_add(1).0;
+        }
         opcode
     }
 
     pub fn reset(&mut self) {
         self.stop = false;
+        self.locked = false;
+        self.fault = None;
+    }
+
+    // locks up or breaks on the running instruction, `run` reports it
+    fn lock_up(&mut self, error: CpuError) {
+        match self.lockup_action {
+            LockupAction::Lock => self.locked = true,
+            LockupAction::Break => self.pc = self.instr_pc,
+        }
+        self.fault = Some(error);
     }
 
     /// Executes the specified instruction on the memory
     pub fn execute(&mut self, instr: Instruction, memory: &mut Memory) {
+        let enable_ime = std::mem::take(&mut self.ime_delay);
         match instr {
-            ErrInstr { opcode } => match opcode {
-                0xD3 | 0xE3 | 0xE4 | 0xF4 | 0xDB | 0xEB | 0xEC | 0xFC | 0xDD | 0xED | 0xFD => {
-                    panic!("Instruction Undefined {opcode:#02X}")
-                }
-                _ => panic!("Invalid Instruction {opcode:#02X}"),
-            },
+            ErrInstr { opcode } if UNDEFINED_OPCODES.contains(&opcode) => {
+                self.lock_up(CpuError::IllegalOpcode { opcode, pc: self.instr_pc });
+            }
             NOP => {
                 ()
             },
-            STOP => self.stop = true,
-            HALT => self.halt = true,
+            STOP => {
+                // STOP is two bytes, the second is skipped
+                self.pc = self.pc.overflowing_add(1).0;
+                if memory.stop() {
+                    // the cpu sits out a speed switch
+                    for _ in 0..SPEED_SWITCH_M_CYCLES {
+                        self.idle(memory);
+                    }
+                } else {
+                    self.stop = true;
+                }
+            }
+            HALT => match self.ime == 0 && interrupt_pending(memory) {
+                true => self.halt_bug = true,
+                false => self.halt = true,
+            },
             DI => self.ime = 0,
-            EI => self.ime = 1,
+            EI => self.ime_delay = true,
             LDRwR(r1, r2) => {
-                let r2_val = self.get_reg(r2, memory);
+                let r2_val = self.read_reg(r2, memory);
                 self.set_reg(r1, r2_val, memory);
             }
             LDRwN(r) => {
@@ -171,8 +298,8 @@ impl SharpSM83 {
                     },
                     );
                 match from {
-                    true => memory.write(loc, self.get_reg_int(A)),
-                    
+                    true => self.write(loc, self.get_reg_int(A), memory),
+
                     false => {
                         let loc_val = self.read(loc, memory);
                         self.set_reg_int(A, loc_val);
@@ -218,6 +345,7 @@ impl SharpSM83 {
                 let msb = high_u16(rr);
                 let lsb = low_u16(rr);
 
+                self.idle(memory);
                 self.sp = self.sp.overflowing_sub(1).0;
                 self.write(self.sp, msb, memory);
                 self.sp = self.sp.overflowing_sub(1).0;
@@ -233,14 +361,16 @@ impl SharpSM83 {
             LDHLwSP => {
                 let e = self.fetch(memory);
                 let result = i16_add(self.get_reg_view(SP) as i16, e as i8 as i16);
+                self.idle(memory);
                 self.set_rr(HL, result.0 as u16);
                 self.set_flags(false, false, result.2, result.1);
             }
             LDSPwHL => {
+                self.idle(memory);
                 self.set_rr(SP, self.get_reg_view(HL));
             }
             DecR(r) => {
-                let rv = self.get_reg(r, memory);
+                let rv = self.read_reg(r, memory);
                 let result = rv.overflowing_sub(1);
                 self.set_reg(r, result.0, memory);
 
@@ -250,7 +380,7 @@ impl SharpSM83 {
                 self.set_flag(FLAG_H, half_c);
             }
             IncR(r) => {
-                let rv = self.get_reg(r, memory);
+                let rv = self.read_reg(r, memory);
                 let result = rv.overflowing_add(1);
                 self.set_reg(r, result.0, memory);
 
@@ -264,7 +394,7 @@ impl SharpSM83 {
                     let n = match instr {
                         Addn | Subn | Andn | Orn | Adcn | Sbcn | Xorn | Cmpn => self.fetch(memory),
                         Add(r) | Sub(r) | And(r) | Or(r) | Adc(r) | Sbc(r) | Xor(r) | Cmp(r) => {
-                            self.get_reg(r, memory)
+                            self.read_reg(r, memory)
                         }
                         _ => panic!("not supposed to get here"),
                     };
@@ -290,6 +420,8 @@ impl SharpSM83 {
             AddSpE => {
                 let e = self.fetch(memory);
                 let result = i16_add(self.sp as i16, e as i8 as i16);
+                self.idle(memory);
+                self.idle(memory);
                 self.sp = result.0 as u16;
                 self.set_flags(false, false, result.2, result.1);
             }
@@ -311,6 +443,7 @@ impl SharpSM83 {
                     _ => panic!("Should not be here"),
                 };
 
+                self.idle(memory);
                 self.set_rr(rr_key, result.0);
             }
 
@@ -394,6 +527,7 @@ impl SharpSM83 {
             JPnn => {
                 let lsb = self.fetch(memory);
                 let msb = self.fetch(memory);
+                self.idle(memory);
                 self.pc = u8_to_u16(msb, lsb);
             }
             JPccnn(cc) => {
@@ -402,6 +536,7 @@ impl SharpSM83 {
                 let nn = u8_to_u16(msb, lsb);
 
                 if self.check_conditions(cc) {
+                    self.idle(memory);
                     self.pc = nn;
                 }
             }
@@ -410,11 +545,13 @@ impl SharpSM83 {
             }
             JRe => {
                 let e = self.fetch(memory) as i8 as i16;
+                self.idle(memory);
                 self.pc = i16_add(self.pc as i16, e).0 as u16;
             }
             JRcce(cc) => {
                 let e = self.fetch(memory) as i8 as i16;
                 if self.check_conditions(cc) {
+                    self.idle(memory);
                     self.pc = i16_add(self.pc as i16, e).0 as u16;
                 }
             }
@@ -428,6 +565,7 @@ impl SharpSM83 {
                     _ => true,
                 };
                 if cc {
+                    self.idle(memory);
                     self.sp = self.sp.overflowing_sub(1).0;
                     self.write(self.sp, high_u16(self.pc), memory);
                     self.sp = self.sp.overflowing_sub(1).0;
@@ -437,6 +575,7 @@ impl SharpSM83 {
                 }
             }
             RSTn(n) => {
+                self.idle(memory);
                 self.sp = self.sp.overflowing_sub(1).0;
                 self.write(self.sp, high_u16(self.pc), memory);
                 self.sp = self.sp.overflowing_sub(1).0;
@@ -451,16 +590,20 @@ impl SharpSM83 {
                 let msb = self.read(self.sp, memory);
                 self.sp = u16_add(self.sp, 1).0;
                 //println!("{:#02X}, {:#02X}, {:#02X}, {:#02X}", self.pc, msb, lsb, self.sp);
+                self.idle(memory);
                 self.pc = u8_to_u16(msb, lsb);
                 //println!("{:#02X}, {:#02X}, {:#02X}, {:#02X}", self.pc, msb, lsb, self.sp);
             }
             RETcc(cc) => {
+                // the condition takes a cycle of its own
+                self.idle(memory);
                 if self.check_conditions(cc) {
                     //println!("{cc:#010b}");
                     let lsb = self.read(self.sp, memory);
                     self.sp = u16_add(self.sp, 1).0;
                     let msb = self.read(self.sp, memory);
                     self.sp = u16_add(self.sp, 1).0;
+                    self.idle(memory);
                     self.pc = u8_to_u16(msb, lsb);    
                 }
             }
@@ -470,20 +613,29 @@ impl SharpSM83 {
                 let msb = self.read(self.sp, memory);
                 self.sp = u16_add(self.sp, 1).0;
                 self.ime = 1;
+                self.idle(memory);
                 self.pc = u8_to_u16(msb, lsb);
             }
             INTn(nn) => {
                 self.ime = 0;
 
+                // two wait states, the pushes and one more to set PC
+                self.idle(memory);
+                self.idle(memory);
                 self.sp = self.sp.overflowing_sub(1).0;
                 self.write(self.sp, high_u16(self.pc), memory);
                 self.sp = self.sp.overflowing_sub(1).0;
                 self.write(self.sp, low_u16(self.pc), memory);
 
+                self.idle(memory);
                 self.pc = nn;
             }
 
-            _ => panic!("Instruction not matched {:?}", instr),
+            _ => self.lock_up(CpuError::Unhandled { instr, pc: self.instr_pc }),
+        }
+        // DI right after EI keeps them disabled
+        if enable_ime && instr != DI {
+            self.ime = 1;
         }
     }
 
@@ -494,115 +646,119 @@ impl SharpSM83 {
     /// Execute the decoded prefix instruction
     fn execute_prefix(&mut self, instr: Instruction, memory: &mut Memory) {
         match instr {
-            RLCr(r) | RRCr(r) | RLr(r) | RRr(r) | SLAr(r) | SRAr(r) | SRLr(r) => {
-                let mut rv = self.get_reg(r, memory);
-                let (new_bit_loc, b) = match instr {
-                    RLCr(_) | RLr(_) | SLAr(_) => (0, (rv & 0x80) >> 7),
-                    RRCr(_) | RRr(_) | SRAr(_) | SRLr(_) => (7, rv & 0x01),
-                    _ => (0, 0),
-                };
-
-                match instr {
-                    RLCr(_) | RLr(_) | SLAr(_) => rv = rv << 1,
-                    RRCr(_) | RRr(_) | SRAr(_) | SRLr(_) => rv = rv >> 1,
-                    _ => (),
-                }
-
-                let c = (self.f & 0x10) >> 4;
-
-                let new_bit = match instr {
-                    RLCr(_) | RRCr(_) => b == 1,
-                    RLr(_) | RRr(_) => c == 1,
-                    SRAr(_) => (rv & 0x40) != 0,
-                    SRLr(_) | SLAr(_) => false,
-                    _ => panic!("nooooooo"),
-                };
-
-                rv = set_bit(rv, new_bit_loc, new_bit);
-
-                self.set_reg(r, rv, memory);
-                self.set_flags(rv == 0, false, false, b == 1);
-            }
+            RLCr(r) => self.shift(r, true, |rv, _| rv & 0x80 != 0, memory),
+            RLr(r) => self.shift(r, true, |_, c| c, memory),
+            SLAr(r) => self.shift(r, true, |_, _| false, memory),
+            RRCr(r) => self.shift(r, false, |rv, _| rv & 0x01 != 0, memory),
+            RRr(r) => self.shift(r, false, |_, c| c, memory),
+            SRAr(r) => self.shift(r, false, |rv, _| rv & 0x80 != 0, memory),
+            SRLr(r) => self.shift(r, false, |_, _| false, memory),
             SWAPr(r) => {
-                let rv = self.get_reg(r, memory);
+                let rv = self.read_reg(r, memory);
                 let n_msb = low_u8(rv) << 4;
                 let n_lsb = high_u8(rv);
                 self.set_reg(r, n_msb + n_lsb, memory);
                 self.set_flags(rv == 0, false, false, false);
             }
             BITnr { n, r } => {
-                let reg_value = self.get_reg(r, memory);
+                let reg_value = self.read_reg(r, memory);
                 let bit = reg_value & (0x1 << n);
                 self.set_flag(FLAG_Z, bit == 0);
                 self.set_flag(FLAG_N, false);
                 self.set_flag(FLAG_H, true);
             }
             RESnr { n, r } => {
-                let reg_val = self.get_reg(r, memory);
+                let reg_val = self.read_reg(r, memory);
                 self.set_reg(r, set_bit(reg_val, n, false), memory);
             }
             SETnr { n, r } => {
-                let reg_val = self.get_reg(r, memory);
+                let reg_val = self.read_reg(r, memory);
                 self.set_reg(r, set_bit(reg_val, n, false), memory);
             }
-            ErrInstr { opcode } => panic!("Should not be here lol {:#0X}", opcode),
-            _ => panic!("also should not be here lmfao"),
+            _ => self.lock_up(CpuError::Unhandled { instr, pc: self.instr_pc }),
         }
     }
+
+    // shifts r by one bit, the bit shifted out goes to carry and `fill` picks the one
+    // shifted in from the old value and carry
+    fn shift(&mut self, r: u8, left: bool, fill: impl Fn(u8, bool) -> bool, memory: &mut Memory) {
+        let rv = self.read_reg(r, memory);
+        let c = self.f & 0x10 != 0;
+        let (shifted, new_bit_loc, b) = match left {
+            true => (rv << 1, 0, (rv & 0x80) >> 7),
+            false => (rv >> 1, 7, rv & 0x01),
+        };
+        let rv = set_bit(shifted, new_bit_loc, fill(rv, c));
+
+        self.set_reg(r, rv, memory);
+        self.set_flags(rv == 0, false, false, b == 1);
+    }
     
 
     /*
      * Memory Converter
+     * Every access takes an M-cycle, the rest of the machine is ticked through
+     * it first so the access lands at the end of its cycle
      */
-    fn read(&mut self, addr: u16, memory: &Memory) -> u8 {
+    fn tick(&mut self, memory: &mut Memory) {
         self.m_cycles += 1;
-        return if !self.rom_control && addr < 0x100 && memory.read(0xFF50) == 0 {
-            self.boot_rom.read(addr)
-        } else {
-            memory.read(addr)
+        for _ in 0..memory.m_cycle_dots() {
+            memory.tick();
         }
     }
 
+    /// An M-cycle without a bus access
+    fn idle(&mut self, memory: &mut Memory) {
+        self.tick(memory);
+    }
+
+    fn read(&mut self, addr: u16, memory: &mut Memory) -> u8 {
+        self.tick(memory);
+        memory.read(addr)
+    }
+
     /*
      * Interrupts
      */
 
     /// Handle Interrupts
+    /// Dispatch takes 5 M-cycles, two wait states, the PC pushes and the jump.
+    /// The interrupt is picked after the high byte push, so a push that lands
+    /// on IE can cancel it and dispatch jumps to 0x0000 instead
     fn handle_interrupt(&mut self, memory: &mut Memory) -> bool {
-        let (if_reg, ie_reg) = (self.read(IF, memory), self.read(IE, memory));
-        //println!("if: {:#10b}, ie: {:#10b}", if_reg, ie_reg);
-        if if_reg & 0b1 > 0 && ie_reg & 0b1 > 0 {
-            //println!("VBlank interrupt");
-            memory.write(IF, if_reg & 0b1111_1110);
-            self.execute(INTn(0x0040), memory);
-            return true;
-        }
-        else if if_reg & 0b10 > 0 && ie_reg & 0b10 > 0 {
-            println!("STAT interrupt");
-            memory.write(IF, if_reg & 0b1111_1101);
-            self.execute(INTn(0x0048), memory);
-            return true;
-        }
-        else if if_reg & 0b100 > 0 && ie_reg & 0b100 > 0 {
-            //println!("Timer interrupt");
-            memory.write(IF, if_reg & 0b1111_1011);
-            self.execute(INTn(0x0050), memory);
-            return true;
-        }
-        else if if_reg & 0b1000 > 0 && ie_reg & 0b1000 > 0 {
-            println!("Serial interrupt");
-            memory.write(IF, if_reg & 0b1111_0111);
-            self.execute(INTn(0x0058), memory);
-            return true;
-        }
-        else if if_reg & 0b10000 > 0 && ie_reg & 0b10000 > 0 {
-            println!("Joypad interrupt");
-            memory.write(IF, if_reg & 0b1110_1111);
-            self.execute(INTn(0x0060), memory);
-            return true;
+        // checked between instructions, not a bus access
+        if !interrupt_pending(memory) {
+            return false;
         }
+        self.ime = 0;
+        self.ime_delay = false;
+        // EI; HALT with an interrupt pending, the handler returns to the HALT
+        let return_pc = match std::mem::take(&mut self.halt_bug) {
+            true => self.pc.wrapping_sub(1),
+            false => self.pc,
+        };
+
+        self.idle(memory);
+        self.idle(memory);
+        self.sp = self.sp.overflowing_sub(1).0;
+        self.write(self.sp, high_u16(return_pc), memory);
+
+        let pending = memory.read(IE) & memory.read(IF) & 0x1F;
+        let vector = match pending {
+            0 => 0x0000,
+            _ => {
+                // lowest bit has the highest priority
+                let bit = pending.trailing_zeros() as u16;
+                memory.write(IF, memory.read(IF) & !(1 << bit));
+                INTERRUPT_VECTORS + bit * 8
+            }
+        };
+        self.sp = self.sp.overflowing_sub(1).0;
+        self.write(self.sp, low_u16(return_pc), memory);
 
-        false
+        self.idle(memory);
+        self.pc = vector;
+        true
     }
 
     ///attempt to decompile the instructions or something (its literally just running, meaning it
@@ -814,8 +970,16 @@ impl SharpSM83 {
         }
     }
 
-    // Gets the internal registers or read from HL
-    pub fn get_reg(&mut self, reg: u8, memory: &Memory) -> u8 {
+    /// Gets the internal registers or peeks at (HL) without taking a cycle
+    pub fn get_reg(&self, reg: u8, memory: &Memory) -> u8 {
+        match reg {
+            READ_HL => memory.read(self.get_reg_view(HL)),
+            _ => self.get_reg_int(reg),
+        }
+    }
+
+    // Gets the internal registers or reads from HL, which takes a cycle
+    fn read_reg(&mut self, reg: u8, memory: &mut Memory) -> u8 {
         match reg {
             B => self.b,
             C => self.c,
@@ -832,12 +996,9 @@ impl SharpSM83 {
         }
     }
 
-    /// Internal cpu write, redundant
+    /// Internal cpu write, ticks the machine like `read`
     fn write(&mut self, addr: u16, byte: u8, memory: &mut Memory) {
-        self.m_cycles += 1;
-        if addr == 0xFF44 {
-            ()
-        }
+        self.tick(memory);
         //println!("wrote {0:02X} at address {1:04X}", byte, addr);
         memory.write(addr, byte);
     }
@@ -863,3 +1024,8 @@ impl core::fmt::Debug for SharpSM83 {
             .finish()
     }
 }
+
+// an interrupt is both requested and enabled
+fn interrupt_pending(memory: &Memory) -> bool {
+    memory.read(IE) & memory.read(IF) & 0x1F != 0
+}
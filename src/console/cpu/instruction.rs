@@ -118,6 +118,23 @@ pub enum Instruction {
 
 use Instruction::*;
 
+// every opcode decoded at compile time, fetches only index these
+static DECODE_TABLE: [Instruction; 256] = decode_table(false);
+static DECODE_CB_TABLE: [Instruction; 256] = decode_table(true);
+
+const fn decode_table(cb: bool) -> [Instruction; 256] {
+    let mut table = [NOP; 256];
+    let mut opcode = 0;
+    while opcode < table.len() {
+        table[opcode] = match cb {
+            true => Instruction::decode_cb_fields(opcode as u8),
+            false => Instruction::decode_fields(opcode as u8),
+        };
+        opcode += 1;
+    }
+    table
+}
+
 impl Instruction {
     // Decode v2
     /*pub fn decodev2(opcode: u8) -> Instruction {
@@ -132,6 +149,16 @@ impl Instruction {
     }*/
 
     pub fn decode(opcode: u8) -> Instruction {
+        DECODE_TABLE[opcode as usize]
+    }
+
+    /// Decode CB prefix instructions
+    pub fn decode_cb(opcode: u8) -> Instruction {
+        DECODE_CB_TABLE[opcode as usize]
+    }
+
+    /// Decodes from the opcode's bit fields, the tables `decode` reads are built with it
+    pub const fn decode_fields(opcode: u8) -> Instruction {
         let op_x = opcode >> 6;
         let op_y = (opcode & 0b00111000) >> 3;
         let op_z = opcode & 0b00000111;
@@ -340,8 +367,8 @@ impl Instruction {
         };
     }
 
-    /// Decode CB prefix instructions
-    pub fn decode_cb(opcode: u8) -> Instruction {
+    /// Decodes CB prefix instructions from their bit fields
+    pub const fn decode_cb_fields(opcode: u8) -> Instruction {
         let op_x = opcode >> 6;
         let op_y = (opcode & 0b00111000) >> 3;
         let op_z = opcode & 0b111;
@@ -0,0 +1,151 @@
+use core::fmt;
+use std::fs::read_to_string;
+use std::io;
+use std::path::Path;
+
+/*
+ *  Rom identification against No-Intro and Redump style DAT files
+ *  https://datomatic.no-intro.org
+ */
+const ROM_STATUS_VERIFIED: &str = "verified";
+const ROM_STATUS_BAD_DUMP: &str = "baddump";
+
+#[derive(Debug)]
+pub enum DatError {
+    Io(io::Error),
+    Xml(roxmltree::Error),
+}
+
+impl fmt::Display for DatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DatError::Io(error) => write!(f, "could not read dat file, {error}"),
+            DatError::Xml(error) => write!(f, "bad dat file, {error}"),
+        }
+    }
+}
+
+impl std::error::Error for DatError {}
+
+/// Hashes of a rom, what DAT entries are matched on
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RomIdentity {
+    pub crc32: u32,
+    /// Lowercase hex
+    pub sha1: String,
+    pub size: usize,
+}
+
+impl RomIdentity {
+    pub fn of(rom: &[u8]) -> RomIdentity {
+        RomIdentity {
+            crc32: crc32fast::hash(rom),
+            sha1: sha1_smol::Sha1::from(rom).digest().to_string(),
+            size: rom.len(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpStatus {
+    /// Checked against more than one cartridge
+    Verified,
+    /// No status given, believed good
+    Good,
+    /// Known to be a bad dump
+    BadDump,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatEntry {
+    /// Canonical name, like "Tetris (World) (Rev 1)"
+    pub name: String,
+    /// From the release element, or the first parenthesis of the name
+    pub region: Option<String>,
+    pub status: DumpStatus,
+    pub crc32: Option<u32>,
+    pub sha1: Option<String>,
+    pub size: Option<usize>,
+}
+
+impl DatEntry {
+    fn matches(&self, identity: &RomIdentity) -> bool {
+        if self.size.is_some_and(|size| size != identity.size) {
+            return false;
+        }
+        match (&self.sha1, self.crc32) {
+            (Some(sha1), _) => *sha1 == identity.sha1,
+            (None, Some(crc32)) => crc32 == identity.crc32,
+            (None, None) => false,
+        }
+    }
+}
+
+/// Every rom listed in a DAT file
+#[derive(Debug, Clone, Default)]
+pub struct Dat {
+    pub name: Option<String>,
+    pub entries: Vec<DatEntry>,
+}
+
+impl Dat {
+    pub fn load(path: &Path) -> Result<Dat, DatError> {
+        let text = read_to_string(path).map_err(DatError::Io)?;
+        Dat::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Dat, DatError> {
+        // Logiqx dats start with a DOCTYPE
+        let options = roxmltree::ParsingOptions { allow_dtd: true, ..Default::default() };
+        let document = roxmltree::Document::parse_with_options(text, options).map_err(DatError::Xml)?;
+        let root = document.root_element();
+        let name = root
+            .children()
+            .find(|node| node.has_tag_name("header"))
+            .and_then(|header| header.children().find(|node| node.has_tag_name("name")))
+            .and_then(|name| name.text())
+            .map(String::from);
+
+        let mut entries = Vec::new();
+        // MAME style dats call games machines
+        let games = root.children().filter(|node| node.has_tag_name("game") || node.has_tag_name("machine"));
+        for game in games {
+            let game_name = game.attribute("name").unwrap_or_default();
+            let region = game
+                .children()
+                .find(|node| node.has_tag_name("release"))
+                .and_then(|release| release.attribute("region"))
+                .map(String::from)
+                .or_else(|| region_from_name(game_name));
+
+            for rom in game.children().filter(|node| node.has_tag_name("rom")) {
+                let status = match rom.attribute("status") {
+                    Some(ROM_STATUS_VERIFIED) => DumpStatus::Verified,
+                    Some(ROM_STATUS_BAD_DUMP) => DumpStatus::BadDump,
+                    _ => DumpStatus::Good,
+                };
+                entries.push(DatEntry {
+                    name: game_name.to_string(),
+                    region: region.clone(),
+                    status,
+                    crc32: rom.attribute("crc").and_then(|crc| u32::from_str_radix(crc, 16).ok()),
+                    sha1: rom.attribute("sha1").map(|sha1| sha1.to_ascii_lowercase()),
+                    size: rom.attribute("size").and_then(|size| size.parse().ok()),
+                });
+            }
+        }
+        Ok(Dat { name, entries })
+    }
+
+    /// Entry for a rom, by SHA-1 when the DAT has one and CRC32 otherwise
+    pub fn identify(&self, identity: &RomIdentity) -> Option<&DatEntry> {
+        self.entries.iter().find(|entry| entry.matches(identity))
+    }
+}
+
+// "Tetris (World) (Rev 1)" is from World
+fn region_from_name(name: &str) -> Option<String> {
+    let start = name.find('(')? + 1;
+    let end = start + name[start..].find(')')?;
+    Some(name[start..end].to_string())
+}
@@ -0,0 +1,83 @@
+/*
+ *  OAM DMA
+ *  https://gbdev.io/pandocs/OAM_DMA_Transfer.html
+ */
+pub const DMA: u16 = 0xFF46;
+
+const OAM_SIZE: u16 = 0xA0;
+// M-cycles between the write to DMA and the first byte being copied
+const STARTUP_DELAY: u8 = 1;
+const DOTS_PER_CYCLE: u8 = 4;
+
+/// Copies 160 bytes into OAM, one byte per M-cycle
+#[derive(Debug, Default)]
+pub struct Dma {
+    reg: u8,
+    source: u16,
+    index: u16,
+    active: bool,
+    // M-cycles left until a requested transfer starts
+    pending: Option<u8>,
+    dots: u8,
+}
+
+impl Dma {
+    pub fn new() -> Dma {
+        Dma::default()
+    }
+
+    pub fn read(&self) -> u8 {
+        self.reg
+    }
+
+    pub fn write(&mut self, byte: u8) {
+        self.reg = byte;
+        self.pending = Some(STARTUP_DELAY);
+    }
+
+    /// True while the transfer owns the bus
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Advances by one dot, returns the source address and OAM offset of the byte to copy when
+    /// one is due
+    pub fn step(&mut self) -> Option<(u16, u16)> {
+        self.dots += 1;
+        if self.dots < DOTS_PER_CYCLE {
+            return None;
+        }
+        self.dots = 0;
+
+        // a restarted transfer keeps the old one running until the new one takes over
+        let mut transfer = None;
+        if self.active {
+            transfer = Some((self.source + self.index, self.index));
+            self.index += 1;
+            self.active = self.index < OAM_SIZE;
+        }
+
+        if let Some(delay) = self.pending {
+            if delay == 0 {
+                self.source = Dma::source(self.reg);
+                self.index = 0;
+                self.active = true;
+                self.pending = None;
+            } else {
+                self.pending = Some(delay - 1);
+            }
+        }
+
+        transfer
+    }
+
+    // 0xE000 and up is echo ram on the external bus, including 0xFE00-0xFFFF
+    fn source(reg: u8) -> u16 {
+        let source = (reg as u16) << 8;
+        if source >= 0xE000 {
+            source - 0x2000
+        } else {
+            source
+        }
+    }
+}
@@ -0,0 +1,106 @@
+use crate::cartridge::{CartridgeError, Mapper, MAX_RAM_SIZE, MAX_ROM_SIZE};
+
+/*
+ *  GBX footer, appended to the rom to describe the cartridge hardware
+ *  https://hhug.me/gbx/1.0
+ */
+const FOOTER_MAGIC: &[u8; 4] = b"GBX!";
+const MAJOR_VERSION: u32 = 1;
+// cartridge info followed by the 16 byte trailer
+pub const GBX_FOOTER_SIZE: usize = 0x40;
+const TRAILER_SIZE: usize = 0x10;
+
+// offsets in the cartridge info
+const MAPPER_ID: usize = 0x00;
+const BATTERY: usize = 0x04;
+const RUMBLE: usize = 0x05;
+const TIMER: usize = 0x06;
+const ROM_SIZE: usize = 0x08;
+const RAM_SIZE: usize = 0x0C;
+const VARIABLES: usize = 0x10;
+pub const GBX_VARIABLES_SIZE: usize = 0x20;
+
+/// Cartridge hardware as the GBX footer describes it, trusted over the header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GbxFooter {
+    pub mapper: Mapper,
+    /// MBC1 wired for multicarts (MB1M)
+    pub multicart: bool,
+    pub battery: bool,
+    pub rumble: bool,
+    pub timer: bool,
+    pub rom_size: usize,
+    pub ram_size: usize,
+    /// Mapper specific settings, left as they are
+    pub variables: [u8; GBX_VARIABLES_SIZE],
+}
+
+impl GbxFooter {
+    /// Splits the footer off the end of a rom file, roms without one come back unchanged
+    pub fn split(mut file: Vec<u8>) -> Result<(Vec<u8>, Option<GbxFooter>), CartridgeError> {
+        if file.len() < GBX_FOOTER_SIZE || !file.ends_with(FOOTER_MAGIC) {
+            return Ok((file, None));
+        }
+        let trailer = &file[file.len() - TRAILER_SIZE..];
+        let word = |data: &[u8], i: usize| u32::from_be_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        let (size, major, minor) = (word(trailer, 0) as usize, word(trailer, 4), word(trailer, 8));
+        if major != MAJOR_VERSION {
+            return Err(CartridgeError::GbxVersion { major, minor });
+        }
+        // later minor versions can only grow the footer
+        if size < GBX_FOOTER_SIZE || size > file.len() {
+            return Err(CartridgeError::GbxFooterSize(size));
+        }
+
+        let start = file.len() - size;
+        let info = &file[start..];
+        let id = &info[MAPPER_ID..MAPPER_ID + 4];
+        let (mapper, multicart) = mapper_from_id(id).ok_or_else(|| {
+            let id = String::from_utf8_lossy(id).trim_end_matches(['\0', ' ']).to_string();
+            CartridgeError::UnknownGbxMapper(id)
+        })?;
+        let mut variables = [0; GBX_VARIABLES_SIZE];
+        variables.copy_from_slice(&info[VARIABLES..VARIABLES + GBX_VARIABLES_SIZE]);
+        // ram gets allocated at the size given, don't let a bogus footer ask for gigabytes
+        let (rom_size, ram_size) = (word(info, ROM_SIZE) as usize, word(info, RAM_SIZE) as usize);
+        if rom_size > MAX_ROM_SIZE {
+            return Err(CartridgeError::GbxRomSize(rom_size));
+        }
+        if ram_size > MAX_RAM_SIZE {
+            return Err(CartridgeError::GbxRamSize(ram_size));
+        }
+        let footer = GbxFooter {
+            mapper,
+            multicart,
+            battery: info[BATTERY] != 0,
+            rumble: info[RUMBLE] != 0,
+            timer: info[TIMER] != 0,
+            rom_size,
+            ram_size,
+            variables,
+        };
+        file.truncate(start);
+        Ok((file, Some(footer)))
+    }
+}
+
+fn mapper_from_id(id: &[u8]) -> Option<(Mapper, bool)> {
+    use Mapper::*;
+    let mapper = match id {
+        b"ROM\0" | b"ROM " => RomOnly,
+        b"MBC1" => Mbc1,
+        b"MB1M" => return Some((Mbc1, true)),
+        b"MBC2" => Mbc2,
+        b"MBC3" => Mbc3,
+        b"MBC5" => Mbc5,
+        b"MBC6" => Mbc6,
+        b"MBC7" => Mbc7,
+        b"MMM1" => Mmm01,
+        b"CAMR" => PocketCamera,
+        b"TAM5" => Tama5,
+        b"HUC3" => HuC3,
+        b"HUC1" => HuC1,
+        _ => return None,
+    };
+    Some((mapper, false))
+}
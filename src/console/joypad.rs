@@ -1,14 +1,10 @@
-const JOYP: u16 = 0xFF00;
-
 use std::collections::HashSet;
 
 use sdl2::keyboard::Keycode;
 
-use crate::{interrupts::JOYPAD_I, test_bit};
-
-use super::memory::Memory;
+use crate::test_bit;
 
-#[derive(Default, Debug)]
+#[derive(Debug)]
 pub struct Joypad {
     start: bool,
     select: bool,
@@ -20,11 +16,30 @@ pub struct Joypad {
     right: bool,
     buttons: u8,
     dpad: u8,
+    // upper nibble of JOYP, bits 4 and 5 select the button groups (active low)
+    joyp: u8,
 }
 
 impl Joypad {
 
-    pub fn update(&mut self, memory: &mut Memory, keys: &HashSet<Keycode>) {
+    pub fn new() -> Joypad {
+        Joypad {
+            start: false,
+            select: false,
+            b: false,
+            a: false,
+            down: false,
+            up: false,
+            left: false,
+            right: false,
+            buttons: 0x0F,
+            dpad: 0x0F,
+            joyp: 0b0011_0000,
+        }
+    }
+
+    /// Returns true when the button state changed and the joypad interrupt should be requested
+    pub fn update(&mut self, keys: &HashSet<Keycode>) -> bool {
         
         self.a = keys.contains(&Keycode::X);
         self.b = keys.contains(&Keycode::Z);
@@ -35,37 +50,40 @@ impl Joypad {
         self.left = keys.contains(&Keycode::Left);
         self.right = keys.contains(&Keycode::Right);
 
-        let joyp = memory.read(JOYP);
-        let sel_buttons = !test_bit!(joyp, 5);
-        let sel_dpad = !test_bit!(joyp, 4);
-
         let dpad = self.dpad_to_bin();
         let buttons = self.buttons_to_bin();
-        
-        let mut data = joyp & 0xF0;
-        if sel_buttons {
-            data |= buttons;
-        }
-        if sel_dpad {
-            data |= dpad;
-        }
-        if !sel_dpad && !sel_buttons {
-            data |= 0x0F;
-        }
-        //memory.request_interrupt(JOYPAD);
+        let mut interrupt = false;
 
         if self.dpad != dpad {
             println!("dpad: {dpad:#010b}");
-            memory.request_interrupt(JOYPAD_I);
+            interrupt = true;
             self.dpad = dpad;
         }
         if self.buttons != buttons {
             println!("buttons: {buttons:#010b}");
-            memory.request_interrupt(JOYPAD_I);
+            interrupt = true;
             self.buttons = buttons;
         }
-        //memory.request_interrupt(JOYPAD_I);
-        memory.write_io(JOYP, data);
+        interrupt
+    }
+
+    pub fn read(&self) -> u8 {
+        let sel_buttons = !test_bit!(self.joyp, 5);
+        let sel_dpad = !test_bit!(self.joyp, 4);
+
+        let mut data = 0x0F;
+        if sel_buttons {
+            data &= self.buttons;
+        }
+        if sel_dpad {
+            data &= self.dpad;
+        }
+        // the top two bits aren't wired and read as 1
+        0xC0 | (self.joyp & 0x30) | data
+    }
+
+    pub fn write(&mut self, byte: u8) {
+        self.joyp = byte & 0xF0;
     }
 
     fn buttons_to_bin(&self) -> u8 {
@@ -87,3 +105,9 @@ impl Joypad {
     }
 
 }
+
+impl Default for Joypad {
+    fn default() -> Joypad {
+        Joypad::new()
+    }
+}
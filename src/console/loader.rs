@@ -0,0 +1,173 @@
+use core::fmt;
+use std::fs::{read, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use flate2::read::GzDecoder;
+
+use crate::cartridge::{CartridgeError, MAX_ROM_SIZE, ROM_BANK_SIZE};
+use crate::cheat::CheatError;
+use crate::patch::PatchError;
+
+/*
+ *  Rom files, plain or packed in an archive, told apart by their magic bytes
+ */
+const ZIP_MAGIC: [u8; 4] = [b'P', b'K', 0x03, 0x04];
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const SEVEN_ZIP_MAGIC: [u8; 6] = [b'7', b'z', 0xBC, 0xAF, 0x27, 0x1C];
+
+// archive entries picked when no name is given
+const ROM_EXTENSIONS: [&str; 3] = ["gb", "gbc", "gbx"];
+// the largest rom with room left for a GBX footer
+const MAX_ROM_FILE_SIZE: usize = MAX_ROM_SIZE + ROM_BANK_SIZE;
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Zip(zip::result::ZipError),
+    SevenZip(sevenz_rust::Error),
+    /// Archive without a .gb, .gbc or .gbx entry
+    NoRom,
+    /// Archive has no entry by the requested name
+    EntryNotFound(String),
+    /// Unpacks to more than any cartridge holds
+    TooLarge,
+    Cartridge(CartridgeError),
+    /// Patch that failed, and why
+    Patch(PathBuf, PatchError),
+    Cheat(CheatError),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(error) => write!(f, "could not read rom, {error}"),
+            LoadError::Zip(error) => write!(f, "bad zip archive, {error}"),
+            LoadError::SevenZip(error) => write!(f, "bad 7z archive, {error}"),
+            LoadError::NoRom => write!(f, "archive has no .gb, .gbc or .gbx file"),
+            LoadError::EntryNotFound(name) => write!(f, "archive has no entry named {name}"),
+            LoadError::TooLarge => write!(f, "rom is larger than any cartridge"),
+            LoadError::Cartridge(error) => write!(f, "{error}"),
+            LoadError::Patch(path, error) => write!(f, "{}: {error}", path.display()),
+            LoadError::Cheat(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Rom data and where it would live if it wasn't packed, saves get named after that path
+#[derive(Debug, Clone)]
+pub struct RomFile {
+    pub data: Vec<u8>,
+    pub path: PathBuf,
+}
+
+impl RomFile {
+    /// Reads a rom, unpacking zip, gzip and 7z archives
+    ///
+    /// `entry` picks an archive member by its name or file name, otherwise the
+    /// first .gb, .gbc or .gbx file is used.
+    pub fn open(path: &Path, entry: Option<&str>) -> Result<RomFile, LoadError> {
+        let mut magic = [0u8; 6];
+        let len = File::open(path).and_then(|mut f| f.read(&mut magic)).map_err(LoadError::Io)?;
+        let magic = &magic[..len];
+
+        let rom = if magic.starts_with(&ZIP_MAGIC) {
+            RomFile::open_zip(path, entry)?
+        } else if magic.starts_with(&GZIP_MAGIC) {
+            RomFile::open_gzip(path)?
+        } else if magic.starts_with(&SEVEN_ZIP_MAGIC) {
+            RomFile::open_7z(path, entry)?
+        } else {
+            let data = read(path).map_err(LoadError::Io)?;
+            RomFile { data, path: path.to_path_buf() }
+        };
+        match rom.data.len() > MAX_ROM_FILE_SIZE {
+            true => Err(LoadError::TooLarge),
+            false => Ok(rom),
+        }
+    }
+
+    fn open_zip(path: &Path, entry: Option<&str>) -> Result<RomFile, LoadError> {
+        let file = File::open(path).map_err(LoadError::Io)?;
+        let mut archive = zip::ZipArchive::new(file).map_err(LoadError::Zip)?;
+        // file_names() is unordered, the first rom has to go by index
+        let names = (0..archive.len())
+            .map(|i| archive.by_index_raw(i).map(|file| file.name().to_string()))
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(LoadError::Zip)?;
+        let name = pick_entry(names.iter().map(String::as_str), entry)?;
+
+        let file = archive.by_name(&name).map_err(LoadError::Zip)?;
+        let size = file.size();
+        let data = read_limited(file, size).map_err(LoadError::Io)?;
+        Ok(RomFile { data, path: unpacked_path(path, &name) })
+    }
+
+    // a gzip stream holds one file, named after the archive minus .gz
+    fn open_gzip(path: &Path) -> Result<RomFile, LoadError> {
+        let file = File::open(path).map_err(LoadError::Io)?;
+        let data = read_limited(GzDecoder::new(file), 0).map_err(LoadError::Io)?;
+        Ok(RomFile { data, path: path.with_extension("") })
+    }
+
+    fn open_7z(path: &Path, entry: Option<&str>) -> Result<RomFile, LoadError> {
+        let mut reader = sevenz_rust::SevenZReader::open(path, sevenz_rust::Password::empty())
+            .map_err(LoadError::SevenZip)?;
+        let names = reader
+            .archive()
+            .files
+            .iter()
+            .filter(|file| !file.is_directory())
+            .map(|file| file.name().to_string())
+            .collect::<Vec<String>>();
+        let name = pick_entry(names.iter().map(String::as_str), entry)?;
+
+        let mut data = Vec::new();
+        reader
+            .for_each_entries(|file, contents| {
+                if file.name() != name {
+                    // entries share one stream, skipping means reading past them
+                    io::copy(contents, &mut io::sink())?;
+                    return Ok(true);
+                }
+                data = read_limited(contents, file.size())?;
+                Ok(false)
+            })
+            .map_err(LoadError::SevenZip)?;
+        Ok(RomFile { data, path: unpacked_path(path, &name) })
+    }
+}
+
+// archives can claim any size and unpack to far more than they take on disk, reading a
+// byte past the limit is enough for `open` to tell a full size rom from a bigger one
+fn read_limited(reader: impl Read, size: u64) -> io::Result<Vec<u8>> {
+    let mut data = Vec::with_capacity(size.min(MAX_ROM_FILE_SIZE as u64) as usize);
+    reader.take(MAX_ROM_FILE_SIZE as u64 + 1).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn pick_entry<'a>(mut names: impl Iterator<Item = &'a str>, entry: Option<&str>) -> Result<String, LoadError> {
+    let found = match entry {
+        Some(entry) => names.find(|name| *name == entry || file_name(name) == entry),
+        None => names.find(|name| {
+            let extension = Path::new(name).extension().and_then(|e| e.to_str()).unwrap_or("");
+            ROM_EXTENSIONS.iter().any(|rom| extension.eq_ignore_ascii_case(rom))
+        }),
+    };
+    match (found, entry) {
+        (Some(name), _) => Ok(name.to_string()),
+        (None, Some(entry)) => Err(LoadError::EntryNotFound(entry.to_string())),
+        (None, None) => Err(LoadError::NoRom),
+    }
+}
+
+fn file_name(name: &str) -> &str {
+    name.rsplit(['/', '\\']).next().unwrap_or(name)
+}
+
+// the entry as if it sat next to the archive
+fn unpacked_path(archive: &Path, name: &str) -> PathBuf {
+    archive.with_file_name(file_name(name))
+}
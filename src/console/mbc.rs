@@ -0,0 +1,131 @@
+pub mod camera;
+pub mod eeprom;
+pub mod huc1;
+pub mod huc3;
+pub mod mbc1;
+pub mod mbc2;
+pub mod mbc3;
+pub mod mbc5;
+pub mod mbc7;
+pub mod rtc;
+pub mod sensor;
+
+use core::fmt;
+
+use crate::cartridge::{RAM_BANK_SIZE, ROM_BANK_SIZE};
+use sensor::ImageSource;
+
+pub use camera::PocketCamera;
+pub use huc1::HuC1;
+pub use huc3::HuC3;
+pub use mbc1::Mbc1;
+pub use mbc2::Mbc2;
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use mbc7::Mbc7;
+
+/// Things happening on the cartridge that the frontend may want to act on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CartridgeEvent {
+    /// Rumble motor turned on or off
+    Rumble(bool),
+    /// Infrared LED turned on or off
+    Infrared(bool),
+    /// HuC3 speaker asked to play a tone, the value is the tone selected by the game
+    Tone(u8),
+}
+
+/// Memory bank controller living on the cartridge
+///
+/// The cartridge owns the rom and ram, the mapper only keeps its registers
+/// and decides which bank an address ends up in.
+pub trait Mbc: fmt::Debug + Send {
+    /// Read from 0x0000-0x7FFF
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8;
+
+    /// Writes to 0x0000-0x7FFF land in the mapper registers
+    fn write_rom(&mut self, addr: u16, byte: u8);
+
+    /// Read from 0xA000-0xBFFF
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8;
+
+    /// Write to 0xA000-0xBFFF
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, byte: u8);
+
+    /// Size of ram built into the mapper, overrides the size from the header
+    fn ram_size(&self) -> Option<usize> {
+        None
+    }
+
+    /// What ram holds before a save is loaded
+    fn ram_fill(&self) -> u8 {
+        0x00
+    }
+
+    /// Mapper state saved after the ram in the .sav file, like the rtc registers
+    fn save_extra(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
+    /// Restores what `save_extra` wrote
+    fn load_extra(&mut self, _data: &[u8]) {}
+
+    /// Whether the rumble motor is currently running
+    fn rumble(&self) -> bool {
+        false
+    }
+
+    /// Light seen by the infrared receiver, ignored by carts without one
+    fn set_infrared(&mut self, _light: bool) {}
+
+    /// Accelerometer reading in g for carts with one, ignored by the others
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+
+    /// Picture source for carts with an image sensor, ignored by the others
+    fn set_image_source(&mut self, _source: Box<dyn ImageSource>) {}
+
+    /// Advances the mapper by one dot, for mappers that take time to do things
+    fn tick(&mut self) {}
+
+    /// Events raised since the last call
+    fn take_events(&mut self) -> Vec<CartridgeEvent> {
+        Vec::new()
+    }
+}
+
+/// Gets a byte from a 16KiB rom bank, wrapping banks past the end of the rom
+pub fn rom_bank_read(rom: &[u8], bank: usize, addr: u16) -> u8 {
+    let banks = (rom.len() / ROM_BANK_SIZE).max(1);
+    let index = (bank % banks) * ROM_BANK_SIZE + (addr as usize & (ROM_BANK_SIZE - 1));
+    *rom.get(index).unwrap_or(&0xFF)
+}
+
+/// Index of an address in a 8KiB ram bank, wrapping banks past the end of the ram
+pub fn ram_bank_index(ram: &[u8], bank: usize, addr: u16) -> usize {
+    (bank * RAM_BANK_SIZE + (addr as usize & (RAM_BANK_SIZE - 1))) % ram.len()
+}
+
+/// Cartridges without a mapper, 32KiB rom and optionally 8KiB of ram
+#[derive(Debug, Default)]
+pub struct RomOnly;
+
+impl Mbc for RomOnly {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        *rom.get(addr as usize).unwrap_or(&0xFF)
+    }
+
+    fn write_rom(&mut self, _addr: u16, _byte: u8) {}
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if ram.is_empty() {
+            return 0xFF;
+        }
+        ram[ram_bank_index(ram, 0, addr)]
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, byte: u8) {
+        if !ram.is_empty() {
+            ram[ram_bank_index(ram, 0, addr)] = byte;
+        }
+    }
+}
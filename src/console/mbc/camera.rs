@@ -0,0 +1,218 @@
+use super::sensor::*;
+use super::*;
+
+// bit 4 of the ram bank register maps the sensor registers instead of ram
+const REGISTERS_MAPPED: u8 = 1 << 4;
+const REGISTER_COUNT: usize = 0x36;
+
+/*
+ *  M64282FP sensor registers
+ *  https://gbdev.io/pandocs/Gameboy_Camera.html
+ */
+const TRIGGER: usize = 0x00;
+// bit 7 N, bits 6-5 VH edge mode, bits 4-0 gain
+const EDGE_MODE: usize = 0x01;
+const EXPOSURE_HIGH: usize = 0x02;
+const EXPOSURE_LOW: usize = 0x03;
+// bits 6-4 edge ratio, bit 3 invert
+const EDGE_RATIO: usize = 0x04;
+// 4x4 matrix of 3 thresholds per pixel
+const DITHER_MATRIX: usize = 0x06;
+
+const CAPTURE_BUSY: u8 = 1 << 0;
+const N_BIT: u8 = 1 << 7;
+const INVERT: u8 = 1 << 3;
+
+// edge enhancement ratios in quarters, 50% to 500%
+const EDGE_RATIOS: [i32; 8] = [2, 3, 4, 5, 8, 12, 16, 20];
+// exposure that passes the sensor image through unchanged
+const UNIT_EXPOSURE: i32 = 0x1000;
+
+// captured image goes to ram bank 0 as 16x14 tiles
+const IMAGE_START: usize = 0x0100;
+const TILES_WIDE: usize = SENSOR_WIDTH / 8;
+const DOTS_PER_CYCLE: u32 = 4;
+
+/// Pocket Camera, 1MiB rom, 128KiB ram and a M64282FP image sensor
+///
+/// https://gbdev.io/pandocs/Gameboy_Camera.html
+#[derive(Debug)]
+pub struct PocketCamera {
+    ram_enabled: bool,
+    // bank 0 can be mapped to 0x4000-0x7FFF
+    rom_bank: u8,
+    ram_bank: u8,
+    registers_mapped: bool,
+    registers: [u8; REGISTER_COUNT],
+    // dots left until the capture in progress finishes
+    busy_dots: u32,
+    source: Option<Box<dyn ImageSource>>,
+}
+
+impl Default for PocketCamera {
+    fn default() -> PocketCamera {
+        PocketCamera::new()
+    }
+}
+
+impl PocketCamera {
+    pub fn new() -> PocketCamera {
+        PocketCamera {
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            registers_mapped: false,
+            registers: [0; REGISTER_COUNT],
+            busy_dots: 0,
+            source: None,
+        }
+    }
+
+    fn exposure(&self) -> i32 {
+        (self.registers[EXPOSURE_HIGH] as i32) << 8 | self.registers[EXPOSURE_LOW] as i32
+    }
+
+    /// Capture length in M-cycles, longer exposures take longer
+    fn capture_cycles(&self) -> u32 {
+        let n = if self.registers[EDGE_MODE] & N_BIT != 0 { 0 } else { 512 };
+        32446 + n + 16 * self.exposure() as u32
+    }
+
+    /// Takes a picture and writes it to ram right away, the busy bit stays set
+    /// for as long as the real sensor would take
+    fn capture(&mut self, ram: &mut [u8]) {
+        let image = match &mut self.source {
+            Some(source) => source.frame(),
+            None => vec![0xFF; SENSOR_SIZE],
+        };
+        let processed = self.process(&image);
+
+        for (y, row) in processed.chunks(SENSOR_WIDTH).enumerate() {
+            for (tile_x, pixels) in row.chunks(8).enumerate() {
+                let tile = (y / 8) * TILES_WIDE + tile_x;
+                let index = IMAGE_START + tile * 16 + (y % 8) * 2;
+                let (mut low, mut high) = (0u8, 0u8);
+                for (i, color) in pixels.iter().enumerate() {
+                    low |= (color & 0b01) << (7 - i);
+                    high |= ((color & 0b10) >> 1) << (7 - i);
+                }
+                if index + 1 < ram.len() {
+                    ram[index] = low;
+                    ram[index + 1] = high;
+                }
+            }
+        }
+        self.busy_dots = self.capture_cycles() * DOTS_PER_CYCLE;
+    }
+
+    /// Exposure, edge enhancement and dithering down to 2 bit colors
+    fn process(&self, image: &[u8]) -> Vec<u8> {
+        let pixel = |x: i32, y: i32| {
+            let x = x.clamp(0, SENSOR_WIDTH as i32 - 1) as usize;
+            let y = y.clamp(0, SENSOR_HEIGHT as i32 - 1) as usize;
+            *image.get(y * SENSOR_WIDTH + x).unwrap_or(&0xFF) as i32
+        };
+        let edge_mode = (self.registers[EDGE_MODE] >> 5) & 0b11;
+        let ratio = EDGE_RATIOS[((self.registers[EDGE_RATIO] >> 4) & 0b111) as usize];
+        let invert = self.registers[EDGE_RATIO] & INVERT != 0;
+        let exposure = self.exposure();
+
+        let mut colors = Vec::with_capacity(SENSOR_SIZE);
+        for y in 0..SENSOR_HEIGHT as i32 {
+            for x in 0..SENSOR_WIDTH as i32 {
+                let p = pixel(x, y);
+                let horizontal = 2 * p - pixel(x - 1, y) - pixel(x + 1, y);
+                let vertical = 2 * p - pixel(x, y - 1) - pixel(x, y + 1);
+                let edge = match edge_mode {
+                    1 => horizontal,
+                    2 => vertical,
+                    3 => horizontal + vertical,
+                    _ => 0,
+                };
+                let mut value = (p + edge * ratio / 4) * exposure / UNIT_EXPOSURE;
+                value = value.clamp(0, 255);
+                if invert {
+                    value = 255 - value;
+                }
+
+                let matrix = DITHER_MATRIX + ((y as usize % 4) * 4 + x as usize % 4) * 3;
+                let thresholds = &self.registers[matrix..matrix + 3];
+                let color = if value < thresholds[0] as i32 {
+                    3
+                } else if value < thresholds[1] as i32 {
+                    2
+                } else if value < thresholds[2] as i32 {
+                    1
+                } else {
+                    0
+                };
+                colors.push(color);
+            }
+        }
+        colors
+    }
+}
+
+impl Mbc for PocketCamera {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_bank_read(rom, 0, addr),
+            _ => rom_bank_read(rom, self.rom_bank as usize, addr),
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x3F,
+            0x4000..=0x5FFF => {
+                self.registers_mapped = byte & REGISTERS_MAPPED != 0;
+                self.ram_bank = byte & 0x0F;
+            }
+            _ => (),
+        }
+    }
+
+    // ram reads work even while it's disabled, only writes are blocked
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if self.registers_mapped {
+            // only the trigger register can be read back
+            return match addr as usize & 0x7F {
+                TRIGGER => {
+                    let busy = if self.busy_dots > 0 { CAPTURE_BUSY } else { 0 };
+                    (self.registers[TRIGGER] & 0b110) | busy
+                }
+                _ => 0x00,
+            };
+        }
+        if ram.is_empty() {
+            return 0xFF;
+        }
+        ram[ram_bank_index(ram, self.ram_bank as usize, addr)]
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, byte: u8) {
+        if self.registers_mapped {
+            let register = addr as usize & 0x7F;
+            if register >= REGISTER_COUNT {
+                return;
+            }
+            self.registers[register] = byte;
+            if register == TRIGGER && byte & CAPTURE_BUSY != 0 && self.busy_dots == 0 {
+                self.capture(ram);
+            }
+            return;
+        }
+        if self.ram_enabled && !ram.is_empty() {
+            ram[ram_bank_index(ram, self.ram_bank as usize, addr)] = byte;
+        }
+    }
+
+    fn tick(&mut self) {
+        self.busy_dots = self.busy_dots.saturating_sub(1);
+    }
+
+    fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.source = Some(source);
+    }
+}
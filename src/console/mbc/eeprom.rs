@@ -0,0 +1,189 @@
+/*
+ *  93LC56 serial EEPROM in 16 bit mode, 128 words
+ *  https://gbdev.io/pandocs/MBC7.html#eeprom
+ */
+pub const EEPROM_SIZE: usize = 256;
+
+// bits of the 0xAx8x register
+pub const CS: u8 = 1 << 7;
+pub const CLK: u8 = 1 << 6;
+pub const DI: u8 = 1 << 1;
+pub const DO: u8 = 1 << 0;
+
+// start bit, 2 bit opcode and 8 address bits
+const COMMAND_BITS: u8 = 11;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    // waiting for the start bit
+    Idle,
+    Command,
+    // shifting a word out on DO, MSB first
+    Reading { word: u16, bits: u8 },
+    // shifting a word in from DI before WRITE or WRAL
+    Writing { address: Option<u8>, bits: u8 },
+}
+
+/// Word storage lives in the cartridge ram, little endian
+#[derive(Debug)]
+pub struct Eeprom {
+    cs: bool,
+    clk: bool,
+    di: bool,
+    data_out: bool,
+    write_enabled: bool,
+    state: State,
+    shift: u16,
+    bits: u8,
+}
+
+impl Default for Eeprom {
+    fn default() -> Eeprom {
+        Eeprom::new()
+    }
+}
+
+impl Eeprom {
+    pub fn new() -> Eeprom {
+        Eeprom {
+            cs: false,
+            clk: false,
+            di: false,
+            data_out: true,
+            write_enabled: false,
+            state: State::Idle,
+            shift: 0,
+            bits: 0,
+        }
+    }
+
+    /// The pins as they read back through 0xAx8x
+    pub fn read(&self) -> u8 {
+        let mut byte = 0;
+        if self.cs {
+            byte |= CS;
+        }
+        if self.clk {
+            byte |= CLK;
+        }
+        if self.di {
+            byte |= DI;
+        }
+        if self.data_out {
+            byte |= DO;
+        }
+        byte
+    }
+
+    /// Sets the pins, DI gets sampled on the rising edge of CLK while CS is high
+    pub fn write(&mut self, storage: &mut [u8], byte: u8) {
+        let cs = byte & CS != 0;
+        let clk = byte & CLK != 0;
+        self.di = byte & DI != 0;
+
+        if !cs {
+            // deselecting aborts whatever was going on, DO reads ready
+            self.state = State::Idle;
+            self.data_out = true;
+        } else if clk && !self.clk && self.cs {
+            self.clock(storage);
+        }
+        self.cs = cs;
+        self.clk = clk;
+    }
+
+    fn clock(&mut self, storage: &mut [u8]) {
+        let bit = self.di as u16;
+        match self.state {
+            State::Idle => {
+                if bit == 1 {
+                    self.state = State::Command;
+                    self.shift = 1;
+                    self.bits = 1;
+                }
+            }
+            State::Command => {
+                self.shift = self.shift << 1 | bit;
+                self.bits += 1;
+                if self.bits == COMMAND_BITS {
+                    self.command(storage);
+                }
+            }
+            State::Reading { word, bits } => {
+                self.data_out = word & 0x8000 != 0;
+                self.state = match bits {
+                    1 => State::Idle,
+                    _ => State::Reading { word: word << 1, bits: bits - 1 },
+                };
+            }
+            State::Writing { address, bits } => {
+                self.shift = self.shift << 1 | bit;
+                if bits > 1 {
+                    self.state = State::Writing { address, bits: bits - 1 };
+                    return;
+                }
+                if self.write_enabled {
+                    match address {
+                        Some(address) => write_word(storage, address, self.shift),
+                        None => (0..128).for_each(|address| write_word(storage, address, self.shift)),
+                    }
+                }
+                // writes finish right away, DO high means ready
+                self.data_out = true;
+                self.state = State::Idle;
+            }
+        }
+    }
+
+    fn command(&mut self, storage: &mut [u8]) {
+        let opcode = (self.shift >> 8) & 0b11;
+        let address = (self.shift & 0x7F) as u8;
+        self.state = State::Idle;
+        match opcode {
+            // READ, a dummy 0 comes out first
+            0b10 => {
+                self.data_out = false;
+                self.state = State::Reading { word: read_word(storage, address), bits: 16 };
+            }
+            // WRITE
+            0b01 => self.state = State::Writing { address: Some(address), bits: 16 },
+            // ERASE
+            0b11 => {
+                if self.write_enabled {
+                    write_word(storage, address, 0xFFFF);
+                }
+                self.data_out = true;
+            }
+            _ => match (self.shift >> 6) & 0b11 {
+                // EWDS
+                0b00 => self.write_enabled = false,
+                // WRAL
+                0b01 => self.state = State::Writing { address: None, bits: 16 },
+                // ERAL
+                0b10 => {
+                    if self.write_enabled {
+                        storage.iter_mut().for_each(|byte| *byte = 0xFF);
+                    }
+                    self.data_out = true;
+                }
+                // EWEN
+                _ => self.write_enabled = true,
+            },
+        }
+    }
+}
+
+fn read_word(storage: &[u8], address: u8) -> u16 {
+    let index = address as usize * 2;
+    match storage.get(index..index + 2) {
+        Some(word) => u16::from_le_bytes([word[0], word[1]]),
+        None => 0xFFFF,
+    }
+}
+
+fn write_word(storage: &mut [u8], address: u8, word: u16) {
+    let index = address as usize * 2;
+    if let Some(bytes) = storage.get_mut(index..index + 2) {
+        bytes.copy_from_slice(&word.to_le_bytes());
+    }
+}
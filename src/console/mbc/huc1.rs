@@ -0,0 +1,78 @@
+use super::*;
+
+// value written to 0x0000-0x1FFF that maps the infrared port at 0xA000
+const IR_MODE: u8 = 0x0E;
+
+/// Hudson HuC1, up to 1MiB rom, 32KiB ram and an infrared transceiver
+///
+/// https://gbdev.io/pandocs/HuC1.html
+#[derive(Debug, Default)]
+pub struct HuC1 {
+    ir_mode: bool,
+    rom_bank: u8,
+    ram_bank: u8,
+    // light seen by the receiver, set from outside
+    ir_light: bool,
+    ir_led: bool,
+    events: Vec<CartridgeEvent>,
+}
+
+impl HuC1 {
+    pub fn new() -> HuC1 {
+        HuC1 {
+            rom_bank: 1,
+            ..HuC1::default()
+        }
+    }
+}
+
+impl Mbc for HuC1 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_bank_read(rom, 0, addr),
+            _ => rom_bank_read(rom, self.rom_bank as usize, addr),
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ir_mode = byte & 0x0F == IR_MODE,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x3F,
+            0x4000..=0x5FFF => self.ram_bank = byte & 0x03,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if self.ir_mode {
+            // bit 0 reads low while light is coming in
+            return if self.ir_light { 0xC0 } else { 0xC1 };
+        }
+        if ram.is_empty() {
+            return 0xFF;
+        }
+        ram[ram_bank_index(ram, self.ram_bank as usize, addr)]
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, byte: u8) {
+        if self.ir_mode {
+            let led = byte & 0x01 != 0;
+            if led != self.ir_led {
+                self.ir_led = led;
+                self.events.push(CartridgeEvent::Infrared(led));
+            }
+            return;
+        }
+        if !ram.is_empty() {
+            ram[ram_bank_index(ram, self.ram_bank as usize, addr)] = byte;
+        }
+    }
+
+    fn set_infrared(&mut self, light: bool) {
+        self.ir_light = light;
+    }
+
+    fn take_events(&mut self) -> Vec<CartridgeEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
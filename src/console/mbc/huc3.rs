@@ -0,0 +1,224 @@
+use super::rtc::Clock;
+use super::*;
+
+// values written to 0x0000-0x1FFF to pick what 0xA000-0xBFFF maps to,
+// anything else maps the ram read only
+const MODE_RAM: u8 = 0x0A;
+const MODE_COMMAND: u8 = 0x0B;
+const MODE_RESPONSE: u8 = 0x0C;
+const MODE_SEMAPHORE: u8 = 0x0D;
+const MODE_IR: u8 = 0x0E;
+
+// commands, upper nibble of a write in command mode
+const READ: u8 = 0x1;
+const WRITE: u8 = 0x3;
+const ADDRESS_LOW: u8 = 0x4;
+const ADDRESS_HIGH: u8 = 0x5;
+const EXTENDED: u8 = 0x6;
+
+// extended commands, lower nibble of an EXTENDED write
+const TIME_TO_MEMORY: u8 = 0x0;
+const MEMORY_TO_TIME: u8 = 0x1;
+const STATUS: u8 = 0x2;
+const PLAY_TONE: u8 = 0xE;
+
+// rtc memory holds the time as 3 nibbles of minutes then 3 nibbles of days
+const TIME_NIBBLES: usize = 6;
+// nibble that picks the tone played by PLAY_TONE
+const TONE_SELECT: usize = 0x27;
+
+const MINUTES_PER_DAY: u64 = 1440;
+
+// minutes u32 + days u32 + u64 timestamp + seconds u32 after the ram in the .sav,
+// saves from before the seconds were kept stop at the timestamp
+pub const HUC3_SAVE_SIZE: usize = 20;
+const HUC3_SAVE_SIZE_NO_SECONDS: usize = 16;
+
+/// Hudson HuC3, up to 2MiB rom, 32KiB ram, a real time clock with a small
+/// nibble memory, a piezo speaker and an infrared transceiver
+///
+/// https://gbdev.io/pandocs/HuC3.html
+#[derive(Debug)]
+pub struct HuC3 {
+    mode: u8,
+    rom_bank: u8,
+    ram_bank: u8,
+
+    clock: Box<dyn Clock>,
+    last_update: u64,
+    seconds: u64,
+    minutes: u16,
+    days: u16,
+    // nibble memory the commands work on
+    memory: [u8; 0x100],
+    address: u8,
+    response: u8,
+
+    ir_light: bool,
+    ir_led: bool,
+    events: Vec<CartridgeEvent>,
+}
+
+impl HuC3 {
+    pub fn new(clock: Box<dyn Clock>) -> HuC3 {
+        let last_update = clock.now();
+        HuC3 {
+            mode: 0,
+            rom_bank: 1,
+            ram_bank: 0,
+            clock,
+            last_update,
+            seconds: 0,
+            minutes: 0,
+            days: 0,
+            memory: [0; 0x100],
+            address: 0,
+            response: 0,
+            ir_light: false,
+            ir_led: false,
+            events: Vec::new(),
+        }
+    }
+
+    /// Minutes into the current day and days counted so far
+    pub fn time(&mut self) -> (u16, u16) {
+        self.update();
+        (self.minutes, self.days)
+    }
+
+    fn update(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_update);
+        self.last_update = now;
+
+        let seconds = self.seconds + elapsed;
+        self.seconds = seconds % 60;
+        let minutes = self.minutes as u64 + seconds / 60;
+        self.minutes = (minutes % MINUTES_PER_DAY) as u16;
+        self.days = ((self.days as u64 + minutes / MINUTES_PER_DAY) & 0xFFF) as u16;
+    }
+
+    fn command(&mut self, byte: u8) {
+        let command = (byte >> 4) & 0x07;
+        let argument = byte & 0x0F;
+        let mut value = argument;
+        match command {
+            READ => {
+                value = self.memory[self.address as usize];
+                self.address = self.address.wrapping_add(1);
+            }
+            WRITE => {
+                self.memory[self.address as usize] = argument;
+                self.address = self.address.wrapping_add(1);
+            }
+            ADDRESS_LOW => self.address = (self.address & 0xF0) | argument,
+            ADDRESS_HIGH => self.address = (self.address & 0x0F) | (argument << 4),
+            EXTENDED => value = self.extended(argument),
+            _ => (),
+        }
+        self.response = (command << 4) | value;
+    }
+
+    fn extended(&mut self, argument: u8) -> u8 {
+        match argument {
+            TIME_TO_MEMORY => {
+                self.update();
+                let time = self.minutes as u32 | (self.days as u32) << 12;
+                for i in 0..TIME_NIBBLES {
+                    self.memory[i] = (time >> (i * 4)) as u8 & 0x0F;
+                }
+            }
+            MEMORY_TO_TIME => {
+                let time = (0..TIME_NIBBLES).fold(0u32, |time, i| time | (self.memory[i] as u32) << (i * 4));
+                self.update();
+                self.minutes = ((time & 0xFFF) as u64 % MINUTES_PER_DAY) as u16;
+                self.days = (time >> 12) as u16 & 0xFFF;
+                self.seconds = 0;
+            }
+            STATUS => return 0x01,
+            PLAY_TONE => self.events.push(CartridgeEvent::Tone(self.memory[TONE_SELECT])),
+            _ => (),
+        }
+        argument
+    }
+}
+
+impl Mbc for HuC3 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_bank_read(rom, 0, addr),
+            _ => rom_bank_read(rom, self.rom_bank as usize, addr),
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.mode = byte & 0x0F,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x7F,
+            0x4000..=0x5FFF => self.ram_bank = byte & 0x03,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        match self.mode {
+            MODE_COMMAND => 0xFF,
+            MODE_RESPONSE => 0x80 | self.response,
+            // commands finish right away, bit 0 set means ready
+            MODE_SEMAPHORE => 0xFF,
+            MODE_IR => if self.ir_light { 0xC0 } else { 0xC1 },
+            _ if ram.is_empty() => 0xFF,
+            _ => ram[ram_bank_index(ram, self.ram_bank as usize, addr)],
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, byte: u8) {
+        match self.mode {
+            MODE_RAM if !ram.is_empty() => ram[ram_bank_index(ram, self.ram_bank as usize, addr)] = byte,
+            MODE_COMMAND => self.command(byte),
+            MODE_IR => {
+                let led = byte & 0x01 != 0;
+                if led != self.ir_led {
+                    self.ir_led = led;
+                    self.events.push(CartridgeEvent::Infrared(led));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn save_extra(&self) -> Vec<u8> {
+        // the counters go with the time they were last brought up to, loading catches them up
+        let mut data = Vec::with_capacity(HUC3_SAVE_SIZE);
+        data.extend_from_slice(&(self.minutes as u32).to_le_bytes());
+        data.extend_from_slice(&(self.days as u32).to_le_bytes());
+        data.extend_from_slice(&self.last_update.to_le_bytes());
+        data.extend_from_slice(&(self.seconds as u32).to_le_bytes());
+        data
+    }
+
+    fn load_extra(&mut self, data: &[u8]) {
+        if data.len() < HUC3_SAVE_SIZE_NO_SECONDS {
+            return;
+        }
+        let word = |i: usize| u32::from_le_bytes([data[i], data[i + 1], data[i + 2], data[i + 3]]);
+        self.minutes = (word(0) as u64 % MINUTES_PER_DAY) as u16;
+        self.days = word(4) as u16 & 0xFFF;
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&data[8..16]);
+        self.last_update = u64::from_le_bytes(timestamp);
+        self.seconds = match data.len() >= HUC3_SAVE_SIZE {
+            true => word(16) as u64 % 60,
+            false => 0,
+        };
+        self.update();
+    }
+
+    fn set_infrared(&mut self, light: bool) {
+        self.ir_light = light;
+    }
+
+    fn take_events(&mut self) -> Vec<CartridgeEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
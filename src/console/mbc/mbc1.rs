@@ -0,0 +1,97 @@
+use crate::cartridge::{NINTENDO_LOGO, ROM_BANK_SIZE};
+
+use super::*;
+
+/// MBC1, up to 2MiB rom and 32KiB ram
+///
+/// https://gbdev.io/pandocs/MBC1.html
+#[derive(Debug)]
+pub struct Mbc1 {
+    ram_enabled: bool,
+    // 5 bit rom bank register, 0x2000-0x3FFF
+    bank1: u8,
+    // 2 bit ram bank / upper rom bank register, 0x4000-0x5FFF
+    bank2: u8,
+    // banking mode select, 0x6000-0x7FFF
+    advanced: bool,
+    // MBC1M wires bank2 to rom bits 4-5 instead of 5-6
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(multicart: bool) -> Mbc1 {
+        Mbc1 {
+            ram_enabled: false,
+            bank1: 1,
+            bank2: 0,
+            advanced: false,
+            multicart,
+        }
+    }
+
+    /// MBC1M carts are 1MiB and have another header with the logo at bank 0x10
+    pub fn is_multicart(rom: &[u8]) -> bool {
+        let logo = 0x10 * ROM_BANK_SIZE + 0x0104;
+        rom.len() == 64 * ROM_BANK_SIZE && rom[logo..logo + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+    }
+
+    fn bank2_shift(&self) -> u8 {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    fn low_bank(&self) -> usize {
+        match self.advanced {
+            true => (self.bank2 << self.bank2_shift()) as usize,
+            false => 0,
+        }
+    }
+
+    fn high_bank(&self) -> usize {
+        let bank1 = if self.multicart { self.bank1 & 0x0F } else { self.bank1 };
+        ((self.bank2 << self.bank2_shift()) | bank1) as usize
+    }
+
+    fn ram_bank(&self) -> usize {
+        match self.advanced {
+            true => self.bank2 as usize,
+            false => 0,
+        }
+    }
+}
+
+impl Mbc for Mbc1 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_bank_read(rom, self.low_bank(), addr),
+            _ => rom_bank_read(rom, self.high_bank(), addr),
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                // bank 0 can't be selected here, the check is on all 5 bits even on MBC1M
+                self.bank1 = byte & 0x1F;
+                if self.bank1 == 0 {
+                    self.bank1 = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.bank2 = byte & 0b11,
+            _ => self.advanced = byte & 0b1 == 1,
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled || ram.is_empty() {
+            return 0xFF;
+        }
+        ram[ram_bank_index(ram, self.ram_bank(), addr)]
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, byte: u8) {
+        if self.ram_enabled && !ram.is_empty() {
+            ram[ram_bank_index(ram, self.ram_bank(), addr)] = byte;
+        }
+    }
+}
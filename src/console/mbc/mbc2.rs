@@ -0,0 +1,69 @@
+use super::*;
+
+const RAM_SIZE: usize = 512;
+
+/// MBC2, up to 256KiB rom and 512 half bytes of ram inside the mapper
+///
+/// https://gbdev.io/pandocs/MBC2.html
+#[derive(Debug)]
+pub struct Mbc2 {
+    ram_enabled: bool,
+    rom_bank: u8,
+}
+
+impl Mbc2 {
+    pub fn new() -> Mbc2 {
+        Mbc2 {
+            ram_enabled: false,
+            rom_bank: 1,
+        }
+    }
+}
+
+impl Default for Mbc2 {
+    fn default() -> Mbc2 {
+        Mbc2::new()
+    }
+}
+
+impl Mbc for Mbc2 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_bank_read(rom, 0, addr),
+            _ => rom_bank_read(rom, self.rom_bank as usize, addr),
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, byte: u8) {
+        // both registers live in 0x0000-0x3FFF, address bit 8 picks which one
+        match addr {
+            0x0000..=0x3FFF if addr & 0x0100 == 0 => self.ram_enabled = byte & 0x0F == 0x0A,
+            0x0000..=0x3FFF => {
+                self.rom_bank = byte & 0x0F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        // only the lower 9 bits are decoded so the ram repeats through 0xA000-0xBFFF,
+        // the upper nibble isn't connected
+        ram[addr as usize & (RAM_SIZE - 1)] | 0xF0
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, byte: u8) {
+        if self.ram_enabled {
+            ram[addr as usize & (RAM_SIZE - 1)] = byte & 0x0F;
+        }
+    }
+
+    fn ram_size(&self) -> Option<usize> {
+        Some(RAM_SIZE)
+    }
+}
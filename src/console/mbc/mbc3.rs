@@ -0,0 +1,94 @@
+use super::rtc::*;
+use super::*;
+
+/// MBC3, up to 2MiB rom, 32KiB ram and an optional real time clock
+///
+/// https://gbdev.io/pandocs/MBC3.html
+#[derive(Debug)]
+pub struct Mbc3 {
+    ram_timer_enabled: bool,
+    rom_bank: u8,
+    // 0x00-0x03 selects a ram bank, 0x08-0x0C an rtc register
+    select: u8,
+    // last byte written to 0x6000-0x7FFF, latching happens on 0x00 -> 0x01
+    latch: u8,
+    rtc: Option<Rtc>,
+}
+
+impl Mbc3 {
+    pub fn new(rtc: Option<Rtc>) -> Mbc3 {
+        Mbc3 {
+            ram_timer_enabled: false,
+            rom_bank: 1,
+            select: 0,
+            latch: 0xFF,
+            rtc,
+        }
+    }
+
+    pub fn rtc(&self) -> Option<&Rtc> {
+        self.rtc.as_ref()
+    }
+}
+
+impl Mbc for Mbc3 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_bank_read(rom, 0, addr),
+            _ => rom_bank_read(rom, self.rom_bank as usize, addr),
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_timer_enabled = byte & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = byte & 0x7F;
+                if self.rom_bank == 0 {
+                    self.rom_bank = 1;
+                }
+            }
+            0x4000..=0x5FFF => self.select = byte & 0x0F,
+            _ => {
+                if self.latch == 0x00 && byte == 0x01 {
+                    if let Some(rtc) = &mut self.rtc {
+                        rtc.latch();
+                    }
+                }
+                self.latch = byte;
+            }
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_timer_enabled {
+            return 0xFF;
+        }
+        match (self.select, &self.rtc) {
+            (0x00..=0x07, _) if !ram.is_empty() => ram[ram_bank_index(ram, self.select as usize, addr)],
+            (0x08..=0x0C, Some(rtc)) => rtc.read(RtcRegister::from_select(self.select).unwrap()),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, byte: u8) {
+        if !self.ram_timer_enabled {
+            return;
+        }
+        match (self.select, &mut self.rtc) {
+            (0x00..=0x07, _) if !ram.is_empty() => ram[ram_bank_index(ram, self.select as usize, addr)] = byte,
+            (0x08..=0x0C, Some(rtc)) => rtc.write(RtcRegister::from_select(self.select).unwrap(), byte),
+            _ => (),
+        }
+    }
+
+    fn save_extra(&self) -> Vec<u8> {
+        self.rtc.as_ref().map_or(Vec::new(), |rtc| rtc.save())
+    }
+
+    fn load_extra(&mut self, data: &[u8]) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.load(data);
+        }
+    }
+}
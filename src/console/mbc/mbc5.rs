@@ -0,0 +1,82 @@
+use super::*;
+
+// rumble carts wire bit 3 of the ram bank register to the motor
+const RUMBLE_MOTOR: u8 = 1 << 3;
+
+/// MBC5, up to 8MiB rom and 128KiB ram
+///
+/// https://gbdev.io/pandocs/MBC5.html
+#[derive(Debug)]
+pub struct Mbc5 {
+    ram_enabled: bool,
+    // 9 bit rom bank, bank 0 can be mapped to 0x4000-0x7FFF
+    rom_bank: u16,
+    ram_bank: u8,
+    has_rumble: bool,
+    rumble: bool,
+    events: Vec<CartridgeEvent>,
+}
+
+impl Mbc5 {
+    pub fn new(has_rumble: bool) -> Mbc5 {
+        Mbc5 {
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            has_rumble,
+            rumble: false,
+            events: Vec::new(),
+        }
+    }
+}
+
+impl Mbc for Mbc5 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_bank_read(rom, 0, addr),
+            _ => rom_bank_read(rom, self.rom_bank as usize, addr),
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enabled = byte == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | byte as u16,
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | (((byte & 0b1) as u16) << 8),
+            0x4000..=0x5FFF => {
+                if self.has_rumble {
+                    let rumble = byte & RUMBLE_MOTOR != 0;
+                    if rumble != self.rumble {
+                        self.rumble = rumble;
+                        self.events.push(CartridgeEvent::Rumble(rumble));
+                    }
+                    self.ram_bank = byte & 0x07;
+                } else {
+                    self.ram_bank = byte & 0x0F;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, ram: &[u8], addr: u16) -> u8 {
+        if !self.ram_enabled || ram.is_empty() {
+            return 0xFF;
+        }
+        ram[ram_bank_index(ram, self.ram_bank as usize, addr)]
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, byte: u8) {
+        if self.ram_enabled && !ram.is_empty() {
+            ram[ram_bank_index(ram, self.ram_bank as usize, addr)] = byte;
+        }
+    }
+
+    fn rumble(&self) -> bool {
+        self.rumble
+    }
+
+    fn take_events(&mut self) -> Vec<CartridgeEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
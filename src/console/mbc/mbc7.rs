@@ -0,0 +1,119 @@
+use super::eeprom::*;
+use super::*;
+
+// accelerometer reading when level, and how far 1g moves it
+const ACCEL_CENTER: u16 = 0x81D0;
+const ACCEL_PER_G: f32 = 0x70 as f32;
+// reading after an erase, before the next latch
+const ACCEL_ERASED: u16 = 0x8000;
+
+/// MBC7, up to 2MiB rom, a 93LC56 EEPROM and a two axis accelerometer
+///
+/// https://gbdev.io/pandocs/MBC7.html
+#[derive(Debug)]
+pub struct Mbc7 {
+    // both have to be set before 0xA000-0xAFFF responds
+    ram_enable1: bool,
+    ram_enable2: bool,
+    rom_bank: u8,
+
+    // tilt in g, positive x is right and positive y is towards the player
+    tilt: (f32, f32),
+    latched: (u16, u16),
+    // 0x55 written to 0xAx0x, the next 0xAA to 0xAx1x latches
+    erased: bool,
+    eeprom: Eeprom,
+}
+
+impl Default for Mbc7 {
+    fn default() -> Mbc7 {
+        Mbc7::new()
+    }
+}
+
+impl Mbc7 {
+    pub fn new() -> Mbc7 {
+        Mbc7 {
+            ram_enable1: false,
+            ram_enable2: false,
+            rom_bank: 1,
+            tilt: (0.0, 0.0),
+            latched: (ACCEL_ERASED, ACCEL_ERASED),
+            erased: false,
+            eeprom: Eeprom::new(),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.ram_enable1 && self.ram_enable2
+    }
+
+    fn accelerometer(&self) -> (u16, u16) {
+        let axis = |g: f32| (ACCEL_CENTER as f32 + g.clamp(-2.0, 2.0) * ACCEL_PER_G) as u16;
+        (axis(self.tilt.0), axis(self.tilt.1))
+    }
+}
+
+impl Mbc for Mbc7 {
+    fn read_rom(&self, rom: &[u8], addr: u16) -> u8 {
+        match addr {
+            0x0000..=0x3FFF => rom_bank_read(rom, 0, addr),
+            _ => rom_bank_read(rom, self.rom_bank as usize, addr),
+        }
+    }
+
+    fn write_rom(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x0000..=0x1FFF => self.ram_enable1 = byte == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = byte & 0x7F,
+            0x4000..=0x5FFF => self.ram_enable2 = byte == 0x40,
+            _ => (),
+        }
+    }
+
+    fn read_ram(&self, _ram: &[u8], addr: u16) -> u8 {
+        if !self.enabled() || addr >= 0xB000 {
+            return 0xFF;
+        }
+        match (addr >> 4) & 0x0F {
+            0x2 => self.latched.0 as u8,
+            0x3 => (self.latched.0 >> 8) as u8,
+            0x4 => self.latched.1 as u8,
+            0x5 => (self.latched.1 >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_ram(&mut self, ram: &mut [u8], addr: u16, byte: u8) {
+        if !self.enabled() || addr >= 0xB000 {
+            return;
+        }
+        match (addr >> 4) & 0x0F {
+            0x0 if byte == 0x55 => {
+                self.erased = true;
+                self.latched = (ACCEL_ERASED, ACCEL_ERASED);
+            }
+            0x1 if byte == 0xAA && self.erased => {
+                self.erased = false;
+                self.latched = self.accelerometer();
+            }
+            0x8 => self.eeprom.write(ram, byte),
+            _ => (),
+        }
+    }
+
+    fn ram_size(&self) -> Option<usize> {
+        Some(EEPROM_SIZE)
+    }
+
+    // an erased 93LC56 reads all ones, games check for that to format it
+    fn ram_fill(&self) -> u8 {
+        0xFF
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x, y);
+    }
+}
@@ -0,0 +1,229 @@
+use core::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Time source for cartridge clocks, in whole seconds
+pub trait Clock: fmt::Debug + Send {
+    fn now(&self) -> u64;
+}
+
+/// Wall clock time
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+// DH register bits
+const DAY_HIGH: u8 = 0b1;
+const HALT: u8 = 1 << 6;
+const CARRY: u8 = 1 << 7;
+
+/// Real time clock registers, 0x08-0x0C on the MBC3 register select
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RtcRegister {
+    Seconds,
+    Minutes,
+    Hours,
+    DayLow,
+    DayHigh,
+}
+
+impl RtcRegister {
+    pub fn from_select(select: u8) -> Option<RtcRegister> {
+        match select {
+            0x08 => Some(RtcRegister::Seconds),
+            0x09 => Some(RtcRegister::Minutes),
+            0x0A => Some(RtcRegister::Hours),
+            0x0B => Some(RtcRegister::DayLow),
+            0x0C => Some(RtcRegister::DayHigh),
+            _ => None,
+        }
+    }
+}
+
+// rtc registers as 5 u32 + latched 5 u32 + u64 timestamp, what VBA-M and BGB append to the .sav
+pub const RTC_SAVE_SIZE: usize = 48;
+
+/// Counts in the background from the clock source and gets copied to the
+/// latched registers for the game to read
+#[derive(Debug)]
+pub struct Rtc {
+    clock: Box<dyn Clock>,
+    // clock time the counters were last brought up to date
+    last_update: u64,
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    halt: bool,
+    carry: bool,
+    latched: [u8; 5],
+}
+
+impl Rtc {
+    pub fn new(clock: Box<dyn Clock>) -> Rtc {
+        let last_update = clock.now();
+        Rtc {
+            clock,
+            last_update,
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halt: false,
+            carry: false,
+            latched: [0; 5],
+        }
+    }
+
+    /// Registers and timestamp in the format other emulators append to the save ram,
+    /// the counters are saved with the time they were last updated and `load` catches them up
+    pub fn save(&self) -> Vec<u8> {
+        let registers = [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days as u8,
+            self.day_high(),
+        ];
+        let mut data = Vec::with_capacity(RTC_SAVE_SIZE);
+        for byte in registers.iter().chain(self.latched.iter()) {
+            data.extend_from_slice(&(*byte as u32).to_le_bytes());
+        }
+        data.extend_from_slice(&self.last_update.to_le_bytes());
+        data
+    }
+
+    /// Restores a saved clock, the time spent turned off gets counted on the next update
+    pub fn load(&mut self, data: &[u8]) {
+        if data.len() < RTC_SAVE_SIZE {
+            return;
+        }
+        let register = |i: usize| data[i * 4];
+        self.seconds = register(0) & 0x3F;
+        self.minutes = register(1) & 0x3F;
+        self.hours = register(2) & 0x1F;
+        self.days = register(3) as u16 | (((register(4) & DAY_HIGH) as u16) << 8);
+        self.halt = register(4) & HALT != 0;
+        self.carry = register(4) & CARRY != 0;
+        for i in 0..5 {
+            self.latched[i] = register(5 + i);
+        }
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&data[40..48]);
+        self.last_update = u64::from_le_bytes(timestamp);
+        self.update();
+    }
+
+    /// Catch the counters up with the clock source
+    pub fn update(&mut self) {
+        let now = self.clock.now();
+        let elapsed = now.saturating_sub(self.last_update);
+        self.last_update = now;
+        if !self.halt {
+            self.advance(elapsed);
+        }
+    }
+
+    fn advance(&mut self, seconds: u64) {
+        // registers can be written with out of range values, those count up to
+        // their bit width and wrap without carrying over on hardware
+        let mut seconds = seconds;
+        while seconds > 0 && (self.seconds >= 60 || self.minutes >= 60 || self.hours >= 24) {
+            self.tick();
+            seconds -= 1;
+        }
+
+        let total = self.seconds as u64
+            + self.minutes as u64 * 60
+            + self.hours as u64 * 3600
+            + self.days as u64 * 86400
+            + seconds;
+        self.seconds = (total % 60) as u8;
+        self.minutes = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+        let days = total / 86400;
+        if days > 0x1FF {
+            self.carry = true;
+        }
+        self.days = (days % 0x200) as u16;
+    }
+
+    fn tick(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+        self.days += 1;
+        if self.days > 0x1FF {
+            self.days = 0;
+            self.carry = true;
+        }
+    }
+
+    /// Copy the running counters into the registers the game can read
+    pub fn latch(&mut self) {
+        self.update();
+        self.latched = [
+            self.seconds,
+            self.minutes,
+            self.hours,
+            self.days as u8,
+            self.day_high(),
+        ];
+    }
+
+    fn day_high(&self) -> u8 {
+        let mut dh = (self.days >> 8) as u8 & DAY_HIGH;
+        if self.halt {
+            dh |= HALT;
+        }
+        if self.carry {
+            dh |= CARRY;
+        }
+        dh
+    }
+
+    pub fn read(&self, register: RtcRegister) -> u8 {
+        match register {
+            RtcRegister::Seconds => self.latched[0] & 0x3F,
+            RtcRegister::Minutes => self.latched[1] & 0x3F,
+            RtcRegister::Hours => self.latched[2] & 0x1F,
+            RtcRegister::DayLow => self.latched[3],
+            RtcRegister::DayHigh => self.latched[4] & (DAY_HIGH | HALT | CARRY),
+        }
+    }
+
+    /// Writes go to the running counters
+    pub fn write(&mut self, register: RtcRegister, byte: u8) {
+        self.update();
+        match register {
+            RtcRegister::Seconds => self.seconds = byte & 0x3F,
+            RtcRegister::Minutes => self.minutes = byte & 0x3F,
+            RtcRegister::Hours => self.hours = byte & 0x1F,
+            RtcRegister::DayLow => self.days = (self.days & 0x100) | byte as u16,
+            RtcRegister::DayHigh => {
+                self.days = (self.days & 0xFF) | (((byte & DAY_HIGH) as u16) << 8);
+                self.halt = byte & HALT != 0;
+                self.carry = byte & CARRY != 0;
+            }
+        }
+    }
+}
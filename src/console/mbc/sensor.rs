@@ -0,0 +1,230 @@
+use core::fmt;
+use std::fs::read;
+use std::io;
+use std::path::Path;
+
+/*
+ *  Images for the Pocket Camera sensor, 8 bit luminance with 0 as black
+ */
+pub const SENSOR_WIDTH: usize = 128;
+pub const SENSOR_HEIGHT: usize = 112;
+pub const SENSOR_SIZE: usize = SENSOR_WIDTH * SENSOR_HEIGHT;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+#[derive(Debug)]
+pub enum ImageError {
+    Io(io::Error),
+    Png(png::DecodingError),
+    Pgm(&'static str),
+    UnknownFormat,
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImageError::Io(error) => write!(f, "could not read image, {error}"),
+            ImageError::Png(error) => write!(f, "bad png, {error}"),
+            ImageError::Pgm(reason) => write!(f, "bad pgm, {reason}"),
+            ImageError::UnknownFormat => write!(f, "image is neither png nor pgm"),
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+/// Where the camera sensor gets its picture from, asked once per capture
+pub trait ImageSource: fmt::Debug + Send {
+    /// SENSOR_WIDTH x SENSOR_HEIGHT luminance values, row by row
+    fn frame(&mut self) -> Vec<u8>;
+}
+
+/// The same picture for every capture
+#[derive(Debug, Clone)]
+pub struct StillImage {
+    pixels: Vec<u8>,
+}
+
+impl StillImage {
+    /// Takes a luminance image of any size and scales it to the sensor
+    pub fn new(width: usize, height: usize, pixels: &[u8]) -> StillImage {
+        StillImage {
+            pixels: scale_to_sensor(width, height, pixels),
+        }
+    }
+
+    /// Loads a PNG or PGM file
+    pub fn open(path: &Path) -> Result<StillImage, ImageError> {
+        let data = read(path).map_err(ImageError::Io)?;
+        let (width, height, pixels) = if data.starts_with(&PNG_SIGNATURE) {
+            decode_png(&data)?
+        } else if data.starts_with(b"P5") || data.starts_with(b"P2") {
+            decode_pgm(&data)?
+        } else {
+            return Err(ImageError::UnknownFormat);
+        };
+        Ok(StillImage::new(width, height, &pixels))
+    }
+}
+
+impl ImageSource for StillImage {
+    fn frame(&mut self) -> Vec<u8> {
+        self.pixels.clone()
+    }
+}
+
+/// Steps through a list of pictures, one per capture, starting over at the end
+#[derive(Debug, Clone)]
+pub struct FrameSequence {
+    frames: Vec<StillImage>,
+    index: usize,
+}
+
+impl FrameSequence {
+    pub fn new(frames: Vec<StillImage>) -> FrameSequence {
+        FrameSequence { frames, index: 0 }
+    }
+
+    pub fn open<P: AsRef<Path>>(paths: &[P]) -> Result<FrameSequence, ImageError> {
+        let frames = paths
+            .iter()
+            .map(|path| StillImage::open(path.as_ref()))
+            .collect::<Result<Vec<StillImage>, ImageError>>()?;
+        Ok(FrameSequence::new(frames))
+    }
+}
+
+impl ImageSource for FrameSequence {
+    fn frame(&mut self) -> Vec<u8> {
+        if self.frames.is_empty() {
+            return vec![0xFF; SENSOR_SIZE];
+        }
+        let frame = self.frames[self.index].frame();
+        self.index = (self.index + 1) % self.frames.len();
+        frame
+    }
+}
+
+/// Asks a closure for every capture, frames of the wrong size get scaled as if they were square
+pub struct CallbackSource<F: FnMut() -> Vec<u8> + Send> {
+    callback: F,
+}
+
+impl<F: FnMut() -> Vec<u8> + Send> CallbackSource<F> {
+    pub fn new(callback: F) -> CallbackSource<F> {
+        CallbackSource { callback }
+    }
+}
+
+impl<F: FnMut() -> Vec<u8> + Send> fmt::Debug for CallbackSource<F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CallbackSource")
+    }
+}
+
+impl<F: FnMut() -> Vec<u8> + Send> ImageSource for CallbackSource<F> {
+    fn frame(&mut self) -> Vec<u8> {
+        let frame = (self.callback)();
+        if frame.len() == SENSOR_SIZE {
+            return frame;
+        }
+        let side = (frame.len() as f64).sqrt() as usize;
+        scale_to_sensor(side, side, &frame)
+    }
+}
+
+// nearest neighbour, missing pixels come out white
+fn scale_to_sensor(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    if width == 0 || height == 0 {
+        return vec![0xFF; SENSOR_SIZE];
+    }
+    let mut scaled = Vec::with_capacity(SENSOR_SIZE);
+    for y in 0..SENSOR_HEIGHT {
+        for x in 0..SENSOR_WIDTH {
+            let sx = x * width / SENSOR_WIDTH;
+            let sy = y * height / SENSOR_HEIGHT;
+            scaled.push(*pixels.get(sy * width + sx).unwrap_or(&0xFF));
+        }
+    }
+    scaled
+}
+
+fn decode_png(data: &[u8]) -> Result<(usize, usize, Vec<u8>), ImageError> {
+    let mut decoder = png::Decoder::new(data);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(ImageError::Png)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(ImageError::Png)?;
+    let channels = info.color_type.samples();
+    let luminance = buffer[..info.buffer_size()]
+        .chunks(channels)
+        .map(|pixel| match pixel {
+            [gray] | [gray, _] => *gray,
+            [r, g, b, ..] => ((*r as u32 * 299 + *g as u32 * 587 + *b as u32 * 114) / 1000) as u8,
+            _ => 0xFF,
+        })
+        .collect();
+    Ok((info.width as usize, info.height as usize, luminance))
+}
+
+// P5 (binary) and P2 (plain) graymaps
+// https://netpbm.sourceforge.net/doc/pgm.html
+fn decode_pgm(data: &[u8]) -> Result<(usize, usize, Vec<u8>), ImageError> {
+    let mut pos = 2;
+    let mut header = [0usize; 3];
+    for value in header.iter_mut() {
+        *value = pgm_number(data, &mut pos).ok_or(ImageError::Pgm("truncated header"))?;
+    }
+    let [width, height, max] = header;
+    if max == 0 || max > 0xFFFF {
+        return Err(ImageError::Pgm("max value out of range"));
+    }
+    // every pixel takes at least a byte in either format
+    let count = width
+        .checked_mul(height)
+        .filter(|count| *count <= data.len())
+        .ok_or(ImageError::Pgm("size larger than the file"))?;
+    let scale = |value: usize| (value.min(max) * 255 / max) as u8;
+
+    let pixels = if data.starts_with(b"P5") {
+        // a single whitespace byte separates the header from the raster
+        let raster = data.get(pos + 1..).ok_or(ImageError::Pgm("missing raster"))?;
+        let bytes = if max > 0xFF { 2 } else { 1 };
+        if raster.len() < count * bytes {
+            return Err(ImageError::Pgm("raster too short"));
+        }
+        raster
+            .chunks(bytes)
+            .take(count)
+            .map(|value| scale(value.iter().fold(0usize, |n, b| n << 8 | *b as usize)))
+            .collect()
+    } else {
+        let mut pixels = Vec::with_capacity(count);
+        for _ in 0..count {
+            let value = pgm_number(data, &mut pos).ok_or(ImageError::Pgm("raster too short"))?;
+            pixels.push(scale(value));
+        }
+        pixels
+    };
+    Ok((width, height, pixels))
+}
+
+// next decimal number, skipping whitespace and # comments
+fn pgm_number(data: &[u8], pos: &mut usize) -> Option<usize> {
+    loop {
+        match data.get(*pos)? {
+            b'#' => {
+                while *data.get(*pos)? != b'\n' {
+                    *pos += 1;
+                }
+            }
+            byte if byte.is_ascii_whitespace() => *pos += 1,
+            _ => break,
+        }
+    }
+    let start = *pos;
+    while data.get(*pos).is_some_and(|b| b.is_ascii_digit()) {
+        *pos += 1;
+    }
+    std::str::from_utf8(&data[start..*pos]).ok()?.parse().ok()
+}
@@ -1,8 +1,11 @@
 use std::cmp::min;
+use std::collections::HashSet;
 use std::fs::File;
 use std::fs::read;
 use std::io::{Write, Result};
 
+use sdl2::keyboard::Keycode;
+
 pub const KBIT: usize = 1024;
 pub const KBYTE: usize = 8 * KBIT;
 pub const BYTE: usize = 8;
@@ -10,123 +13,378 @@ pub const BYTE: usize = 8;
 pub const GLOBAL_START: u16 = 0x30;
 pub const BOOT_ROM_PATH: &str = "./gb_boot/DMG_ROM.gb";
 
-const DMA: u16 = 0xFF46;
 const JOYP: u16 = 0xFF00;
-use super::regids::IF;
+const KEY1: u16 = 0xFF4D;
+const BOOT: u16 = 0xFF50;
+use super::regids::{IF, IE, DIV};
+use super::cpu::DOTS_PER_M_CYCLE;
+use super::interrupts::{JOYPAD_I, SERIAL_I, TIMER_I};
+use super::boot::{check_boot_rom_size, post_boot_div, post_boot_io, Model, CGB_BOOT_ROM_SIZE};
+use super::cartridge::Cartridge;
+use super::cheat::{Cheats, SharkBank};
+use super::power::{PowerOnState, Region};
+use super::dma::{Dma, DMA};
+use super::apu::Apu;
+use super::joypad::Joypad;
+use super::ppu::PPU;
+use super::serial::Serial;
+use super::timer::HTimer;
+
+/*
+ *  Memory map
+ *  https://gbdev.io/pandocs/Memory_Map.html
+ */
+const ROM_END: u16 = 0x7FFF;
+const VRAM_START: u16 = 0x8000;
+const VRAM_END: u16 = 0x9FFF;
+const ERAM_START: u16 = 0xA000;
+const ERAM_END: u16 = 0xBFFF;
+const WRAM_START: u16 = 0xC000;
+const WRAM_END: u16 = 0xDFFF;
+const ECHO_START: u16 = 0xE000;
+const ECHO_END: u16 = 0xFDFF;
+const OAM_START: u16 = 0xFE00;
+const OAM_END: u16 = 0xFE9F;
+const UNUSABLE_START: u16 = 0xFEA0;
+const UNUSABLE_END: u16 = 0xFEFF;
+const IO_START: u16 = 0xFF00;
+const IO_END: u16 = 0xFF7F;
+const HRAM_START: u16 = 0xFF80;
+const HRAM_END: u16 = 0xFFFE;
 
-// size in bits
-#[derive(Debug, Clone)]
+/// The memory bus, reads and writes get decoded by address and handed to
+/// whatever owns that region
 pub struct Memory {
-    data: Vec<u8>,
+    // without a cartridge the rom and external ram areas are plain ram
+    cartridge: Option<Cartridge>,
+    rom: Vec<u8>,
+    eram: Vec<u8>,
+    // mapped over the cartridge until a write to 0xFF50
+    boot_rom: Option<Vec<u8>>,
+    cheats: Cheats,
+
+    wram: Vec<u8>,
+    hram: Vec<u8>,
+    if_reg: u8,
+    ie: u8,
+
+    pub ppu: PPU,
+    pub joypad: Joypad,
+    timer: HTimer,
+    serial: Serial,
+    apu: Apu,
+    dma: Dma,
+
+    // CGB only registers exist
+    cgb: bool,
+    // CGB speed switch, bit 7 is the current speed and bit 0 arms a switch
+    key1: u8,
+    // STOP mode, the clock is off until a button is pressed
+    stopped: bool,
 }
 
 impl Memory {
 
+    /// `size` bytes of plain ram stand in for the cartridge rom until one is inserted
     pub fn new(size: usize) -> Memory {
-        let mut data = vec![0x00; size];
-        if size > 0xFF00 {
-            data[0xFF00] = 0b00110000;
-        }
         Memory {
-            data,
+            cartridge: None,
+            rom: vec![0x00; min(size, ROM_END as usize + 1)],
+            eram: vec![0x00; (ERAM_END - ERAM_START + 1) as usize],
+            boot_rom: None,
+            cheats: Cheats::new(),
+
+            wram: vec![0x00; (WRAM_END - WRAM_START + 1) as usize],
+            hram: vec![0x00; (HRAM_END - HRAM_START + 1) as usize],
+            if_reg: 0x00,
+            ie: 0x00,
+
+            ppu: PPU::new(),
+            joypad: Joypad::new(),
+            timer: HTimer::new(),
+            serial: Serial::new(),
+            apu: Apu::new(),
+            dma: Dma::new(),
+
+            cgb: false,
+            key1: 0x00,
+            stopped: false,
         }
     }
 
     pub fn from_file(size: usize, rom_path: &str) -> Memory {
-        let mut data = vec![0x00; size];
+        let mut memory = Memory::new(size);
         match read(rom_path) {
             Ok(buffer) => {
                 //load default boot rom
-                for i in 0..min(buffer.len(), size) {
-                    data[i] = buffer[i];
-                }
+                let len = min(buffer.len(), memory.rom.len());
+                memory.rom[..len].copy_from_slice(&buffer[..len]);
             },
             Err(error) => panic!("{error} boot rom error, file not found or incorrect file"),
         }
+        memory
+    }
 
-        Memory {
-            data
+    pub fn insert_cartridge(&mut self, cartridge: Cartridge) {
+        self.cartridge = Some(cartridge);
+    }
+
+    /// Fills work ram, HRAM and OAM the way `state` says they power on
+    pub fn power_on(&mut self, state: &PowerOnState) {
+        self.wram = state.contents(Region::Wram, self.wram.len());
+        self.hram = state.contents(Region::Hram, self.hram.len());
+        let oam = state.contents(Region::Oam, (OAM_END - OAM_START + 1) as usize);
+        for (offset, byte) in oam.into_iter().enumerate() {
+            self.ppu.dma_write(offset as u16, byte);
         }
+    }
 
+    /// Maps a DMG (256 byte) or CGB (2304 byte) boot rom over the start of the cartridge,
+    /// other sizes are rejected and leave the mapping as it was
+    pub fn load_boot_rom(&mut self, boot_rom: Vec<u8>) -> Result<()> {
+        check_boot_rom_size(boot_rom.len())?;
+        self.cgb = boot_rom.len() == CGB_BOOT_ROM_SIZE;
+        self.boot_rom = Some(boot_rom);
+        Ok(())
     }
 
-    pub fn get_data(&self) -> Vec<u8> {
-        self.data.clone()
+    /// Whether the boot rom is still mapped
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom.is_some()
+    }
+
+    pub fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+
+    pub fn cheats_mut(&mut self) -> &mut Cheats {
+        &mut self.cheats
+    }
+
+    /// GameShark codes, written once a frame by the frame loop
+    pub fn apply_cheats(&mut self) {
+        for (bank, addr, value) in self.cheats.shark_writes() {
+            match (bank, &mut self.cartridge) {
+                (SharkBank::Ram(bank), Some(cartridge)) if (ERAM_START..=ERAM_END).contains(&addr) => {
+                    cartridge.write_ram_bank(bank as usize, addr, value)
+                }
+                // there's a single work ram bank without CGB banking, which 0 and 1 both select
+                (SharkBank::Wram(bank), _) if bank > 1 && (0xD000..=WRAM_END).contains(&addr) => (),
+                _ => self.write_bus(addr, value),
+            }
+        }
+    }
+
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.cartridge.as_ref()
     }
 
-    pub fn read_io(&self, addr: u16) -> u8 {
-        self.data[addr as usize]
+    pub fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        self.cartridge.as_mut()
     }
+
+    /// Everything visible on the bus from 0x0000 to 0xFFFF
+    pub fn get_data(&self) -> Vec<u8> {
+        (0..=0xFFFF).map(|addr| self.read_bus(addr)).collect()
+    }
+
+    /// CPU side read, a DMA transfer owns everything below the io registers while it runs
     pub fn read(&self, addr: u16) -> u8 {
-        if  addr == 0xFF02 {
-            //println!("reading serial ");
-            //println!("readd joyp, {:#010b}", self.data[addr as usize]);
-            //self.data[addr as usize] = (byte & 0xF0) + (self.data[addr as usize] & 0x0F);
+        if self.dma.active() && addr < IO_START {
+            return 0xFF;
         }
-        self.data[addr as usize]
+        self.read_bus(addr)
     }
 
-    pub fn write(&mut self, addr: u16, byte: u8){
-        if  addr == 0xFF02 {
-            //println!("writing serial ctrl {:#010b}", byte);
+    /// CPU side write, io registers, HRAM and IE are all that's reachable during DMA
+    pub fn write(&mut self, addr: u16, byte: u8) {
+        if self.dma.active() && addr < IO_START {
+            return;
+        }
+        self.write_bus(addr, byte);
+    }
+
+    fn read_bus(&self, addr: u16) -> u8 {
+        match addr {
+            0x0000..=ROM_END => match (self.read_boot_rom(addr), &self.cartridge) {
+                (Some(byte), _) => byte,
+                (None, Some(cartridge)) => self.cheats.genie_read(addr, cartridge.read_rom(addr)),
+                (None, None) => *self.rom.get(addr as usize).unwrap_or(&0xFF),
+            },
+            VRAM_START..=VRAM_END => self.ppu.read(addr),
+            ERAM_START..=ERAM_END => match &self.cartridge {
+                Some(cartridge) => cartridge.read_ram(addr),
+                None => self.eram[(addr - ERAM_START) as usize],
+            },
+            WRAM_START..=WRAM_END => self.wram[(addr - WRAM_START) as usize],
+            ECHO_START..=ECHO_END => self.wram[(addr - ECHO_START) as usize],
+            OAM_START..=OAM_END => self.ppu.read(addr),
+            UNUSABLE_START..=UNUSABLE_END => 0xFF,
+            IO_START..=IO_END => self.read_io(addr),
+            HRAM_START..=HRAM_END => self.hram[(addr - HRAM_START) as usize],
+            IE => self.ie,
         }
-        if  addr == 0xFF01 {
-            //println!("writing serial data {:#010b}", byte);
+    }
+
+    fn write_bus(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x0000..=ROM_END => match &mut self.cartridge {
+                Some(cartridge) => cartridge.write_rom(addr, byte),
+                None => {
+                    if let Some(b) = self.rom.get_mut(addr as usize) {
+                        *b = byte;
+                    }
+                }
+            },
+            VRAM_START..=VRAM_END => self.ppu.write(addr, byte),
+            ERAM_START..=ERAM_END => match &mut self.cartridge {
+                Some(cartridge) => cartridge.write_ram(addr, byte),
+                None => self.eram[(addr - ERAM_START) as usize] = byte,
+            },
+            WRAM_START..=WRAM_END => self.wram[(addr - WRAM_START) as usize] = byte,
+            ECHO_START..=ECHO_END => self.wram[(addr - ECHO_START) as usize] = byte,
+            OAM_START..=OAM_END => self.ppu.write(addr, byte),
+            UNUSABLE_START..=UNUSABLE_END => (),
+            IO_START..=IO_END => self.write_io(addr, byte),
+            HRAM_START..=HRAM_END => self.hram[(addr - HRAM_START) as usize] = byte,
+            IE => self.ie = byte,
         }
-        if addr >= 0xC000 && addr <= 0xDDFF {
-            self.data[(addr) as usize] = byte;
-            self.data[(addr + 0x2000) as usize] = byte;
+    }
+
+    // the CGB boot rom leaves a hole for the cartridge header at 0x0100-0x01FF
+    fn read_boot_rom(&self, addr: u16) -> Option<u8> {
+        let boot_rom = self.boot_rom.as_ref()?;
+        match addr {
+            0x0000..=0x00FF => Some(boot_rom[addr as usize]),
+            0x0200..=0x08FF if boot_rom.len() == CGB_BOOT_ROM_SIZE => Some(boot_rom[addr as usize]),
+            _ => None,
         }
-        if addr >= 0xE000 && addr <= 0xFDFF {
-            self.data[(addr - 0x2000) as usize] = byte;
-            self.data[(addr) as usize] = byte;
+    }
 
+    fn read_io(&self, addr: u16) -> u8 {
+        match addr {
+            JOYP => self.joypad.read(),
+            0xFF01..=0xFF02 => self.serial.read(addr),
+            0xFF04..=0xFF07 => self.timer.read(addr),
+            // only the five interrupt bits exist
+            IF => self.if_reg | 0xE0,
+            0xFF10..=0xFF3F => self.apu.read(addr),
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B => self.ppu.read(addr),
+            DMA => self.dma.read(),
+            KEY1 if self.cgb => self.key1 | 0x7E,
+            // nothing drives the bus for unmapped registers
+            _ => 0xFF,
         }
+    }
+
+    fn write_io(&mut self, addr: u16, byte: u8) {
         match addr {
-            0xFF42 => {
-                self.data[addr as usize] |= byte & 0b1111_1100;
+            JOYP => self.joypad.write(byte),
+            0xFF01..=0xFF02 => self.serial.write(addr, byte),
+            0xFF04..=0xFF07 => self.timer.write(addr, byte),
+            IF => self.if_reg = byte & 0x1F,
+            0xFF10..=0xFF3F => self.apu.write(addr, byte),
+            0xFF40..=0xFF45 | 0xFF47..=0xFF4B => self.ppu.write(addr, byte),
+            DMA => self.dma.write(byte),
+            // the speed bit only changes through STOP
+            KEY1 if self.cgb => self.key1 = self.key1 & 0x80 | byte & 0x01,
+            // unmapping the boot rom is one way, it can't be mapped back in
+            BOOT if byte != 0 => self.boot_rom = None,
+            _ => (),
+        }
+    }
+
+    /// Advances the devices on the bus by one dot
+    pub fn tick(&mut self) {
+        if let Some(cartridge) = &mut self.cartridge {
+            cartridge.tick();
+        }
+        // the cartridge keeps its own time, everything else is off in STOP
+        if self.stopped {
+            return;
+        }
+
+        // DMA, the timer and serial run off the cpu clock, twice a dot in double speed
+        let cpu_clocks = if self.double_speed() { 2 } else { 1 };
+        for _ in 0..cpu_clocks {
+            if let Some((source, offset)) = self.dma.step() {
+                let byte = self.read_bus(source);
+                self.ppu.dma_write(offset, byte);
             }
-            DMA => {
-                self.dma_transfer(byte);
-                self.data[addr as usize] = byte
+            if self.timer.update(false) {
+                self.request_interrupt(TIMER_I);
+            }
+            if self.serial.update() {
+                self.request_interrupt(SERIAL_I);
             }
-            JOYP => {
-                //println!("writing normal {:#010b}, {:#010b}", byte, self.data[addr as usize]);
-                self.data[addr as usize] = (byte & 0xF0) + (self.data[addr as usize] & 0x0F);
-            },
-            _ => self.data[addr as usize] = byte,
         }
+        self.ppu.set_oam_locked(self.dma.active());
+        let interrupts = self.ppu.update();
+        self.request_interrupt(interrupts);
+    }
+
+    /// STOP resets DIV, then on CGB switches speed if KEY1 armed it and returns
+    /// true. Otherwise the clock stops and the LCD goes blank until `wake`
+    pub fn stop(&mut self) -> bool {
+        self.timer.write(DIV, 0);
+        if self.cgb && self.key1 & 0x01 != 0 {
+            self.key1 = !self.key1 & 0x80;
+            return true;
+        }
+        self.stopped = true;
+        self.ppu.blank();
+        false
+    }
+
+    /// Leaves STOP mode, a selected joypad line went low
+    pub fn wake(&mut self) {
+        self.stopped = false;
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped
     }
 
-    pub fn write_io(&mut self, addr: u16, byte:u8) {
-        //println!("writing io {:#010b}, {:#010b}", byte, self.data[addr as usize]);
-        self.data[addr as usize] = byte;
+    /// CGB double speed mode, the cpu and its devices run twice as fast
+    pub fn double_speed(&self) -> bool {
+        self.key1 & 0x80 != 0
     }
 
-    fn dma_transfer(&mut self, source: u8) {
-        let length = 0x9F;
-        let source = (source as u16) << 8;
-        let dest = 0xFE00;
+    /// Dots an M-cycle takes at the current speed
+    pub fn m_cycle_dots(&self) -> usize {
+        match self.double_speed() {
+            true => DOTS_PER_M_CYCLE / 2,
+            false => DOTS_PER_M_CYCLE,
+        }
+    }
 
-        for i in 0..length {
-            let byte = self.read(source + i);
-            self.write(dest + i, byte);
+    /// Leaves the I/O registers the way the boot rom of `model` does
+    pub fn skip_boot(&mut self, model: Model) {
+        self.cgb = model == Model::Cgb;
+        for (addr, byte) in post_boot_io(model) {
+            self.write(addr, byte);
+        }
+        self.timer.set_div(post_boot_div(model));
+    }
+
+    /// Latches the host keyboard state into the joypad
+    pub fn update_joypad(&mut self, keys: &HashSet<Keycode>) {
+        if self.joypad.update(keys) {
+            self.request_interrupt(JOYPAD_I);
         }
     }
 
     pub fn request_interrupt(&mut self, interrupt: u8) {
-        let if_old = self.read(IF);
-        let if_new = if_old | interrupt;
-        self.write(IF, if_new);
+        self.if_reg |= interrupt;
     }
 
     pub fn log(&self) -> Result<()> {
         let mut f = File::create("memory.hex")?;
         println!("Creating hex dump");
-        for i in (0..self.data.len()).step_by(16){
+        for i in (0..0x10000).step_by(16){
             write!(f, "{:04X}:", i)?;
             for j in 0..16 {
-                write!(f, " {:02X}", self.data[i + j])?;
+                write!(f, " {:02X}", self.read_bus((i + j) as u16))?;
             }
             writeln!(f, "")?;
         }
@@ -151,11 +409,10 @@ impl Memory {
                     println!("");
                     break;
                 }
-                print!(" {:02X}", self.data[index as usize]);
+                print!(" {:02X}", self.read_bus(index));
                 std::io::stdout().flush().unwrap();
             }
             println!("");
         }
     }
 }
-
@@ -0,0 +1,278 @@
+use core::fmt;
+use std::fs::read;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/*
+ *  Soft patches, applied to the rom in memory so the file on disk never changes
+ *  IPS: https://zerosoft.zophar.net/ips.php
+ *  UPS: https://www.romhacking.net/documents/392/
+ *  BPS: https://www.romhacking.net/documents/746/
+ */
+const IPS_MAGIC: &[u8] = b"PATCH";
+const IPS_EOF: &[u8] = b"EOF";
+const UPS_MAGIC: &[u8] = b"UPS1";
+const BPS_MAGIC: &[u8] = b"BPS1";
+
+// source, target and patch crc32 at the end of UPS and BPS files
+const FOOTER_SIZE: usize = 12;
+
+// the largest cartridge rom, bigger targets are corrupt patches
+const MAX_TARGET_SIZE: usize = 0x80_0000;
+
+// patches next to the rom, the first one found is used
+const PATCH_EXTENSIONS: [&str; 3] = ["ips", "ups", "bps"];
+
+#[derive(Debug)]
+pub enum PatchError {
+    Io(io::Error),
+    UnknownFormat,
+    /// Patch ends early or points outside the rom
+    Malformed(&'static str),
+    /// A UPS or BPS crc32 doesn't match, `which` is source, target or patch
+    Checksum { which: &'static str, expected: u32, actual: u32 },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::Io(error) => write!(f, "could not read patch, {error}"),
+            PatchError::UnknownFormat => write!(f, "patch is not an IPS, UPS or BPS file"),
+            PatchError::Malformed(reason) => write!(f, "bad patch, {reason}"),
+            PatchError::Checksum { which, expected, actual } => {
+                write!(f, "{which} checksum mismatch, expected {expected:08X} got {actual:08X}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// Patch file sharing the rom's name, like game.ips for game.gb. Hacks are often shipped in
+/// several formats of the same patch, so only the first of IPS, UPS and BPS is picked
+pub fn find_patch(rom_path: &Path) -> Option<PathBuf> {
+    PATCH_EXTENSIONS
+        .iter()
+        .map(|extension| rom_path.with_extension(extension))
+        .find(|path| path.is_file())
+}
+
+/// Reads a patch file and applies it to a copy of `rom`
+pub fn apply_file(rom: &[u8], path: &Path) -> Result<Vec<u8>, PatchError> {
+    let patch = read(path).map_err(PatchError::Io)?;
+    apply(rom, &patch)
+}
+
+/// Applies an IPS, UPS or BPS patch, told apart by their magic bytes
+pub fn apply(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(UPS_MAGIC) {
+        apply_ups(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(PatchError::UnknownFormat)
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], offset: usize) -> Reader<'a> {
+        Reader { data, offset }
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], PatchError> {
+        let bytes = self
+            .offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(self.offset..end))
+            .ok_or(PatchError::Malformed("unexpected end of patch"))?;
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, PatchError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // big endian, IPS only
+    fn number(&mut self, len: usize) -> Result<usize, PatchError> {
+        Ok(self.bytes(len)?.iter().fold(0, |n, byte| n << 8 | *byte as usize))
+    }
+
+    // UPS and BPS variable length numbers, 7 bits per byte with the top bit ending it
+    fn varint(&mut self) -> Result<usize, PatchError> {
+        let too_large = || PatchError::Malformed("number too large");
+        let mut value = 0usize;
+        let mut shift = 1usize;
+        loop {
+            let byte = self.byte()?;
+            value = ((byte & 0x7F) as usize)
+                .checked_mul(shift)
+                .and_then(|bits| value.checked_add(bits))
+                .ok_or_else(too_large)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            // another 7 bits would shift out of a usize
+            if shift.leading_zeros() < 7 {
+                return Err(too_large());
+            }
+            shift <<= 7;
+            value = value.checked_add(shift).ok_or_else(too_large)?;
+        }
+    }
+}
+
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut output = rom.to_vec();
+    let mut reader = Reader::new(patch, IPS_MAGIC.len());
+    loop {
+        if reader.data[reader.offset..].starts_with(IPS_EOF) {
+            reader.offset += IPS_EOF.len();
+            break;
+        }
+        let offset = reader.number(3)?;
+        let (len, fill) = match reader.number(2)? {
+            // run length encoded record
+            0 => (reader.number(2)?, Some(reader.byte()?)),
+            len => (len, None),
+        };
+        if output.len() < offset + len {
+            output.resize(offset + len, 0);
+        }
+        match fill {
+            Some(byte) => output[offset..offset + len].fill(byte),
+            None => output[offset..offset + len].copy_from_slice(reader.bytes(len)?),
+        }
+    }
+    // some patches shrink the rom with a size after EOF
+    if let Ok(size) = reader.number(3) {
+        output.truncate(size);
+    }
+    Ok(output)
+}
+
+fn apply_ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let (source_crc, target_crc) = check_footer(patch)?;
+    let end = patch.len() - FOOTER_SIZE;
+    let mut reader = Reader::new(&patch[..end], UPS_MAGIC.len());
+    let source_size = reader.varint()?;
+    let target_size = check_size(reader.varint()?)?;
+    check_crc("source", rom.len() == source_size, source_crc, rom)?;
+
+    let mut output = rom.to_vec();
+    output.resize(target_size, 0);
+    let mut offset = 0usize;
+    while reader.offset < end {
+        offset = offset.checked_add(reader.varint()?).ok_or(PatchError::Malformed("offset out of range"))?;
+        // xor bytes until a zero, which skips one more byte
+        loop {
+            let byte = reader.byte()?;
+            if byte != 0 {
+                if let Some(out) = output.get_mut(offset) {
+                    *out ^= byte;
+                }
+            }
+            offset = offset.checked_add(1).ok_or(PatchError::Malformed("offset out of range"))?;
+            if byte == 0 {
+                break;
+            }
+        }
+    }
+    check_crc("target", true, target_crc, &output)?;
+    Ok(output)
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let (source_crc, target_crc) = check_footer(patch)?;
+    let end = patch.len() - FOOTER_SIZE;
+    let mut reader = Reader::new(&patch[..end], BPS_MAGIC.len());
+    let source_size = reader.varint()?;
+    let target_size = check_size(reader.varint()?)?;
+    let metadata_size = reader.varint()?;
+    reader.bytes(metadata_size)?;
+    check_crc("source", rom.len() == source_size, source_crc, rom)?;
+
+    let mut output = Vec::with_capacity(target_size);
+    let mut source_offset = 0isize;
+    let mut target_offset = 0isize;
+    while reader.offset < end {
+        let data = reader.varint()?;
+        let len = (data >> 2) + 1;
+        // target copies feed on their own output, so the size has to be held to before each one
+        if output.len().checked_add(len).is_none_or(|size| size > target_size) {
+            return Err(PatchError::Malformed("output larger than the target"));
+        }
+        match data & 0b11 {
+            // SourceRead, the source at the same position as the output
+            0 => {
+                let start = output.len();
+                let bytes = rom.get(start..start + len).ok_or(PatchError::Malformed("source read out of range"))?;
+                output.extend_from_slice(bytes);
+            }
+            // TargetRead
+            1 => output.extend_from_slice(reader.bytes(len)?),
+            // SourceCopy and TargetCopy move their offset relative to the last copy
+            command => {
+                let relative = reader.varint()?;
+                let out_of_range = || PatchError::Malformed("relative offset out of range");
+                let delta = if relative & 1 != 0 { -((relative >> 1) as isize) } else { (relative >> 1) as isize };
+                if command == 2 {
+                    source_offset = source_offset.checked_add(delta).ok_or_else(out_of_range)?;
+                    let start = usize::try_from(source_offset).map_err(|_| PatchError::Malformed("source copy out of range"))?;
+                    let bytes = rom.get(start..start + len).ok_or(PatchError::Malformed("source copy out of range"))?;
+                    output.extend_from_slice(bytes);
+                    source_offset += len as isize;
+                } else {
+                    target_offset = target_offset.checked_add(delta).ok_or_else(out_of_range)?;
+                    // byte by byte, the copy can overlap what it writes
+                    for _ in 0..len {
+                        let byte = usize::try_from(target_offset)
+                            .ok()
+                            .and_then(|i| output.get(i).copied())
+                            .ok_or(PatchError::Malformed("target copy out of range"))?;
+                        output.push(byte);
+                        target_offset += 1;
+                    }
+                }
+            }
+        }
+    }
+    if output.len() != target_size {
+        return Err(PatchError::Malformed("output size doesn't match the patch"));
+    }
+    check_crc("target", true, target_crc, &output)?;
+    Ok(output)
+}
+
+// checks the patch's own crc and returns the source and target ones
+fn check_footer(patch: &[u8]) -> Result<(u32, u32), PatchError> {
+    if patch.len() < UPS_MAGIC.len() + FOOTER_SIZE {
+        return Err(PatchError::Malformed("unexpected end of patch"));
+    }
+    let footer = &patch[patch.len() - FOOTER_SIZE..];
+    let crc = |i: usize| u32::from_le_bytes([footer[i], footer[i + 1], footer[i + 2], footer[i + 3]]);
+    check_crc("patch", true, crc(8), &patch[..patch.len() - 4])?;
+    Ok((crc(0), crc(4)))
+}
+
+fn check_size(size: usize) -> Result<usize, PatchError> {
+    match size > MAX_TARGET_SIZE {
+        true => Err(PatchError::Malformed("target too large")),
+        false => Ok(size),
+    }
+}
+
+fn check_crc(which: &'static str, size_matches: bool, expected: u32, data: &[u8]) -> Result<(), PatchError> {
+    let actual = crc32fast::hash(data);
+    if !size_matches || actual != expected {
+        return Err(PatchError::Checksum { which, expected, actual });
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/*
+ *  Power on contents of ram and registers
+ *  Real units come up with whatever the cells settle to, which some games read
+ *  https://gbdev.io/pandocs/Power_Up_Sequence.html
+ */
+
+// stripe length of the hardware like work ram pattern
+const STRIPE_SIZE: usize = 0x80;
+
+/// What ram and cpu registers hold before anything writes them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PowerOnFill {
+    #[default]
+    Zeros,
+    Ones,
+    /// Every byte from the seeded generator
+    Random,
+    /// Work ram in alternating 0x00 and 0xFF stripes with about one bit in
+    /// sixteen flipped, HRAM and OAM random. An approximation of DMG units.
+    HardwareLike,
+}
+
+impl FromStr for PowerOnFill {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<PowerOnFill, String> {
+        match s.to_ascii_lowercase().as_str() {
+            "zeros" => Ok(PowerOnFill::Zeros),
+            "ones" | "ff" => Ok(PowerOnFill::Ones),
+            "random" => Ok(PowerOnFill::Random),
+            "hardware" => Ok(PowerOnFill::HardwareLike),
+            _ => Err(format!("unknown power on fill {s}, expected zeros, ones, random or hardware")),
+        }
+    }
+}
+
+impl fmt::Display for PowerOnFill {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            PowerOnFill::Zeros => "zeros",
+            PowerOnFill::Ones => "ones",
+            PowerOnFill::Random => "random",
+            PowerOnFill::HardwareLike => "hardware",
+        };
+        write!(f, "{name}")
+    }
+}
+
+/// Ram regions that get filled at power on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Wram,
+    Hram,
+    Oam,
+    Registers,
+}
+
+/// A fill and the seed it was made from, the same seed gives the same contents
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PowerOnState {
+    pub fill: PowerOnFill,
+    pub seed: u64,
+}
+
+impl PowerOnState {
+    /// Without a seed one is taken from the clock, read it back from `seed` to repeat a run
+    pub fn new(fill: PowerOnFill, seed: Option<u64>) -> PowerOnState {
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_nanos() as u64)
+        });
+        PowerOnState { fill, seed }
+    }
+
+    /// Contents of `len` bytes of `region`, each region has its own stream so
+    /// resizing one doesn't change the others
+    pub fn contents(&self, region: Region, len: usize) -> Vec<u8> {
+        let mut rng = XorShift::new(self.seed ^ (region as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+        match (self.fill, region) {
+            (PowerOnFill::Zeros, _) => vec![0x00; len],
+            (PowerOnFill::Ones, _) => vec![0xFF; len],
+            (PowerOnFill::HardwareLike, Region::Wram) => (0..len)
+                .map(|i| {
+                    let stripe = if (i / STRIPE_SIZE).is_multiple_of(2) { 0x00 } else { 0xFF };
+                    // each bit set with a chance of 1 in 16
+                    let flips = rng.next_u64() & rng.next_u64() & rng.next_u64() & rng.next_u64();
+                    stripe ^ flips as u8
+                })
+                .collect(),
+            _ => (0..len).map(|_| rng.next_u64() as u8).collect(),
+        }
+    }
+}
+
+/// xorshift64*, small and good enough for filling ram
+#[derive(Debug, Clone)]
+pub struct XorShift {
+    state: u64,
+}
+
+impl XorShift {
+    pub fn new(seed: u64) -> XorShift {
+        // splitmix the seed so 0 and nearby seeds still give unrelated streams
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        XorShift { state: if z == 0 { 1 } else { z } }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+}
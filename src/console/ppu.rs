@@ -1,5 +1,3 @@
-use super::regids::IF;
-use crate::console::Memory;
 use crate::{console::*, test_bit};
 
 use interrupts::{STAT_I, VBLANK_I};
@@ -22,13 +20,12 @@ const TMA_1: u16 = 0x9C00;
 //registsers
 const LCDC: u16 = 0xFF40;
 const LY: u16 = 0xFF44;
-const LYC: u16 = 0xFF46;
+const LYC: u16 = 0xFF45;
 const STAT: u16 = 0xFF41;
 const SCY: u16 = 0xFF42;
 const SCX: u16 = 0xFF43;
 const WY: u16 = 0xFF4A;
 const WX: u16 = 0xFF4B;
-const DMA: u16 = 0xFF46;
 const BGP: u16 = 0xFF47;
 const OBP0: u16 = 0xFF48;
 const OBP1: u16 = 0xFF49;
@@ -94,6 +91,9 @@ impl Pixel {
 }
 
 pub struct PPU {
+    vram: Vec<u8>,
+    oam: Vec<u8>,
+
     dots: usize,
     mode: u8,
 
@@ -115,6 +115,8 @@ pub struct PPU {
     obp1: u8,
 
     stat_int: bool,
+    // set while OAM DMA owns the object attribute memory
+    oam_locked: bool,
 
     //objects
     objects: Vec<u16>,
@@ -127,6 +129,9 @@ pub struct PPU {
 impl PPU {
     pub fn new() -> PPU {
         PPU {
+            vram: vec![0x00; 0x2000],
+            oam: vec![0x00; 0xA0],
+
             dots: 0,
             mode: 0,
 
@@ -146,6 +151,7 @@ impl PPU {
             obp1: 0u8,
 
             stat_int: false,
+            oam_locked: false,
 
             objects: Vec::new(),
             bg_fifo: VecDeque::new(),
@@ -154,24 +160,60 @@ impl PPU {
         }
     }
 
-    fn update_registers(&mut self, memory: &Memory) {
-        self.lcdc = memory.read(LCDC);
-        self.lyc = memory.read(LYC);
-        self.stat = memory.read(STAT);
-        self.scy = memory.read(SCY);
-        self.scx = memory.read(SCX);
-        self.wy = memory.read(WY);
-        self.wx = memory.read(WX);
-        self.bgp = memory.read(BGP);
-        self.obp0 = memory.read(OBP0);
-        self.obp1 = memory.read(OBP1);
+    /// VRAM, OAM and the LCD registers as seen from the bus
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            0x8000..=0x9FFF => self.vram[(addr - 0x8000) as usize],
+            0xFE00..=0xFE9F if self.oam_locked => 0xFF,
+            0xFE00..=0xFE9F => self.oam[(addr - 0xFE00) as usize],
+            LCDC => self.lcdc,
+            STAT => {
+                let lyc_eq_ly = if self.ly == self.lyc { LYC_EQ_LY } else { 0 };
+                // bit 7 is unused and always reads as 1
+                0x80 | (self.stat & 0b0111_1000) | lyc_eq_ly | (self.mode & PPU_MODE)
+            }
+            SCY => self.scy,
+            SCX => self.scx,
+            LY => self.ly,
+            LYC => self.lyc,
+            BGP => self.bgp,
+            OBP0 => self.obp0,
+            OBP1 => self.obp1,
+            WY => self.wy,
+            WX => self.wx,
+            _ => 0xFF,
+        }
+    }
+
+    /// Byte `offset` of an OAM DMA transfer, goes in even while OAM is locked
+    pub fn dma_write(&mut self, offset: u16, byte: u8) {
+        self.oam[offset as usize] = byte;
     }
 
-    fn set_registers(&mut self, memory: &mut Memory) {
-        memory.write(LY, self.ly);
+    pub fn set_oam_locked(&mut self, locked: bool) {
+        self.oam_locked = locked;
     }
 
-    fn get_tile(&mut self, memory: &mut Memory) -> u16 {
+    pub fn write(&mut self, addr: u16, byte: u8) {
+        match addr {
+            0x8000..=0x9FFF => self.vram[(addr - 0x8000) as usize] = byte,
+            0xFE00..=0xFE9F => self.oam[(addr - 0xFE00) as usize] = byte,
+            LCDC => self.lcdc = byte,
+            // mode and lyc == ly bits are read only
+            STAT => self.stat = (self.stat & 0b0000_0111) | (byte & 0b0111_1000),
+            SCY => self.scy = byte,
+            SCX => self.scx = byte,
+            LYC => self.lyc = byte,
+            BGP => self.bgp = byte,
+            OBP0 => self.obp0 = byte,
+            OBP1 => self.obp1 = byte,
+            WY => self.wy = byte,
+            WX => self.wx = byte,
+            _ => (),
+        }
+    }
+
+    fn get_tile(&mut self) -> u16 {
         //in_window is not working :)
         let in_window = self.fx < self.wx / 8
             && self.fx + 1 >= self.wx / 8
@@ -181,10 +223,10 @@ impl PPU {
         let win_tma = if self.check_lcdc(WIN_TM) { TMA_0 } else { TMA_1 };
         let bg_tma = if !self.check_lcdc(BG_TM) { TMA_0 } else { TMA_1 };
         let tma = if self.check_lcdc(WIN_EN) && in_window { win_tma } else { bg_tma };
-        self.scy = 0;
-        self.scx = 0;
+        // scrolling isn't hooked up to the fetcher yet
+        let scy = 0u8;
         //self.fy = self.scy;
-        self.fy = ((self.ly as u16 + self.scy as u16) & 0xFF) as u8;
+        self.fy = ((self.ly as u16 + scy as u16) & 0xFF) as u8;
 
         if in_window {
             //self.fx = (self.fx + self.wx.overflowing_sub(7).0 / 8) & 0x1F;
@@ -195,16 +237,16 @@ impl PPU {
         let loc = tma + (self.fx / 8) as u16 + 32 * block_y;
         //println!("{loc:#04X}, fetcher: {}, {}", self.fx, block_y);
 
-        memory.read(loc) as u16
+        self.read(loc) as u16
     }
 
-    fn get_obj(&mut self, memory: &mut Memory) -> Vec<u16> {
+    fn get_obj(&mut self) -> Vec<u16> {
         let mut valid_objects = Vec::new();
-        let ly = memory.read(LY) as u16;
+        let ly = self.ly as u16;
         // 0 for 1 tile, 1 for 2 tiles
         let range = if !self.check_lcdc(OBJ_S) { 8 } else { 16 };
         for addr in (0xFE00..0xFE9F).step_by(4) {
-            let y = memory.read(addr) as u16;
+            let y = self.read(addr) as u16;
             let y_max = y + range as u16;
             //means the object is on the current scanline
             if ly + 16 >= y && ly + 16 < y_max {
@@ -243,8 +285,9 @@ impl PPU {
         pixels
     }
 
-    pub fn update(&mut self, memory: &mut Memory) {
-        self.update_registers(memory);
+    /// Advances one dot, returns the interrupts to request
+    pub fn update(&mut self) -> u8 {
+        let mut interrupts = 0;
 
         if !self.check_lcdc(BGWIN_EN) {
             //println!("here")
@@ -255,9 +298,9 @@ impl PPU {
             //return
         }
 
-        // Mode 2
-        if self.dots < 80 {
-            self.oam_scan(memory);
+        // Mode 2, objects are picked once for the whole line rather than on each of its 80 dots
+        if self.dots == 0 {
+            self.oam_scan();
         }
 
         // prepare for drawing pixels (mode 3)
@@ -268,7 +311,7 @@ impl PPU {
         }
 
         if self.dots >= 80 && self.mode == 3 && self.ly < 144 {
-            self.draw_pixels(memory);
+            self.draw_pixels();
         }
 
         if self.mode == 0 {
@@ -276,14 +319,14 @@ impl PPU {
         }
 
         if self.ly >= 144 {
-            memory.request_interrupt(VBLANK_I);
+            interrupts |= VBLANK_I;
             self.mode = 1;
         }
 
         if self.lyc == self.ly {
             self.stat |= 0b100;
             if test_bit!(self.stat, 6) && !self.stat_int {
-                memory.request_interrupt(STAT_I);
+                interrupts |= STAT_I;
                 self.stat_int = true;
             }
         } else {
@@ -293,7 +336,7 @@ impl PPU {
         if ((self.mode == 0 && test_bit!(self.stat, 3)) 
             || (self.mode == 1 && test_bit!(self.stat, 4))
             || (self.mode == 2 && test_bit!(self.stat, 5))) && !self.stat_int {
-            memory.request_interrupt(STAT_I);
+            interrupts |= STAT_I;
             self.stat_int = true;
         }
 
@@ -304,17 +347,17 @@ impl PPU {
             self.dots = 0;
         }
 
-        self.set_registers(memory);
+        interrupts
     }
 
 
-    fn oam_scan(&mut self, memory: &mut Memory) {
+    fn oam_scan(&mut self) {
         self.mode = 2;
         if self.objects.len() < 10 {
-            let mut found_objects = self.get_obj(memory);
+            let mut found_objects = self.get_obj();
             found_objects.sort_by(|a, b| {
-                let xa = memory.read(a + 1).overflowing_sub(8).0;
-                let xb = memory.read(b + 1).overflowing_sub(8).0;
+                let xa = self.read(a + 1).overflowing_sub(8).0;
+                let xb = self.read(b + 1).overflowing_sub(8).0;
                 xa.partial_cmp(&xb).unwrap()
             });
             for i in 0..min(found_objects.len(), 10) {
@@ -325,10 +368,10 @@ impl PPU {
         }
     }
 
-    fn draw_pixels(&mut self, memory: &mut Memory) {
+    fn draw_pixels(&mut self) {
 
         // get the background pixel
-        let tile_index = self.get_tile(memory);
+        let tile_index = self.get_tile();
         let mut vram_bank = if test_bit!(self.lcdc, 4) {
             if tile_index < 128 { VB_0 } else { VB_0 }
         } else {
@@ -339,39 +382,39 @@ impl PPU {
         }
         let index_low = vram_bank + tile_index * 16 + (self.fy as u16 % 8) * 2;
         let index_high = index_low + 1;
-        let low = memory.read(index_low);
-        let high = memory.read(index_high);
+        let low = self.read(index_low);
+        let high = self.read(index_high);
         let pixels = PPU::mix_bytes(low, high);
         let pix_idx = self.fx % 8;
-        let bgp = memory.read(BGP);
+        let bgp = self.read(BGP);
         self.bg_fifo.push_back(Pixel::new(pixels[pix_idx as usize], bgp, 0, 0));
 
         // push object
         for addr in &self.objects {
-            //let y = memory.read(addr + 0);
-            let x = memory.read(*addr + 1);
+            //let y = self.read(addr + 0);
+            let x = self.read(*addr + 1);
             let x_min = self.fx as u16;
             let x_max = x_min + 8;
             if x as u16 > x_min && (x as u16) <= x_max {
                 let xdiff = x_max - x as u16;
                 //obj_counter += 1;
-                let attributes = memory.read(*addr + 3);
+                let attributes = self.read(*addr + 3);
                 let y_flip = test_bit!(attributes, 6);
                 let x_flip = test_bit!(attributes, 5);
 
-                let obj_ti = memory.read(*addr + 2) as u16;
+                let obj_ti = self.read(*addr + 2) as u16;
 
-                let y = memory.read(*addr) as u16;
+                let y = self.read(*addr) as u16;
                 let diff =
                     ((self.fy as u16).overflowing_sub(y).0.overflowing_add(16).0) % 8;
                 let obj_internal_y = if !y_flip { diff * 2 } else { (8 - diff) * 2 };
 
                 let index_low = VB_0 + obj_ti * 16 + obj_internal_y;
                 let index_high = index_low + 1;
-                let low = memory.read(index_low);
-                let high = memory.read(index_high);
+                let low = self.read(index_low);
+                let high = self.read(index_high);
 
-                let palette = memory.read(if test_bit!(attributes, 5) {OBP0} else {OBP1});
+                let palette = self.read(if test_bit!(attributes, 5) {OBP0} else {OBP1});
 
                 let obj_pixels = PPU::mix_bytes_obj(low, high, y_flip, x_flip);
                 self.obj_fifo.push_back(Pixel::new(
@@ -433,6 +476,14 @@ impl PPU {
         }
     }
 
+    /// Shows what a switched off LCD does, nothing
+    pub fn blank(&mut self) {
+        let [r, g, b, a] = PALETTE[0];
+        for pixel in self.bg.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[a, b, g, r]);
+        }
+    }
+
     pub fn is_ready(&self) -> bool {
         return self.ly == 153;
     }
@@ -0,0 +1,47 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Battery backed ram on disk, a raw dump of the cartridge ram like other emulators use
+#[derive(Debug, Clone)]
+pub struct SaveFile {
+    path: PathBuf,
+}
+
+impl SaveFile {
+    pub fn new(path: PathBuf) -> SaveFile {
+        SaveFile { path }
+    }
+
+    /// `game.gb` saves to `game.sav`
+    pub fn for_rom(rom_path: &Path) -> SaveFile {
+        SaveFile::new(rom_path.with_extension("sav"))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// None when there is no save yet
+    pub fn load(&self) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(data) => Ok(Some(data)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Writes to a temporary file next to the save and renames it over the old one,
+    /// so a crash halfway through leaves the previous save intact
+    pub fn write(&self, data: &[u8]) -> io::Result<()> {
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+
+        let mut file = File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp, &self.path)
+    }
+}
@@ -0,0 +1,52 @@
+const SB: u16 = 0xFF01;
+const SC: u16 = 0xFF02;
+
+/// Serial port, there is no link cable so transfers go to stdout
+#[derive(Debug)]
+pub struct Serial {
+    sb: u8,
+    sc: u8,
+}
+
+impl Serial {
+    pub fn new() -> Serial {
+        Serial {
+            sb: 0x00,
+            sc: 0x00,
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            SB => self.sb,
+            // only the transfer start and clock select bits exist
+            SC => self.sc | 0x7E,
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, addr: u16, byte: u8) {
+        match addr {
+            SB => self.sb = byte,
+            SC => self.sc = byte,
+            _ => (),
+        }
+    }
+
+    /// Returns true when a transfer finished and the serial interrupt should be requested
+    pub fn update(&mut self) -> bool {
+        if self.sc == 0x81 {
+            print!("{}", self.sb as char);
+            self.sc = 0x01;
+            self.sb = 0xFF;
+            return true;
+        }
+        false
+    }
+}
+
+impl Default for Serial {
+    fn default() -> Serial {
+        Serial::new()
+    }
+}
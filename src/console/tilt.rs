@@ -0,0 +1,30 @@
+use std::collections::HashSet;
+
+use sdl2::keyboard::Keycode;
+
+/// Host side tilt for cartridges with an accelerometer, in g
+///
+/// Positive x tilts the right side down, positive y tilts the bottom edge
+/// towards the player.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Tilt {
+    pub x: f32,
+    pub y: f32,
+}
+
+impl Tilt {
+    pub fn new(x: f32, y: f32) -> Tilt {
+        Tilt { x, y }
+    }
+
+    /// I J K L tilt all the way in their direction, opposite keys cancel out
+    pub fn from_keys(keys: &HashSet<Keycode>) -> Tilt {
+        let axis = |minus: Keycode, plus: Keycode| {
+            keys.contains(&plus) as i8 as f32 - keys.contains(&minus) as i8 as f32
+        };
+        Tilt {
+            x: axis(Keycode::J, Keycode::L),
+            y: axis(Keycode::I, Keycode::K),
+        }
+    }
+}
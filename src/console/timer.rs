@@ -1,14 +1,12 @@
-use interrupts::TIMER_I;
-
-use crate::{console::*, test_bit};
+use crate::test_bit;
 
 const DIV: u16 = 0xFF04;
 const TIMA: u16 = 0xFF05;
 const TMA: u16 = 0xFF06;
 const TAC: u16 = 0xFF07;
 
-//const CLOCK_SPEEDS: [usize; 4] = [1024, 16, 64, 256];
-const CLOCK_SPEEDS: [usize; 4] = [256, 4, 16, 64];
+// in dots, the cpu ticks four of them per M-cycle
+const CLOCK_SPEEDS: [usize; 4] = [1024, 16, 64, 256];
 const DIV_SPEED: usize = 256;
 
 pub struct HTimer {
@@ -28,26 +26,43 @@ impl HTimer {
             div: 0,
             tima: 0u8,
             tma: 0u8,
-            tac: 0xf8u8,
+            tac: 0u8,
+        }
+    }
+
+    pub fn read(&self, addr: u16) -> u8 {
+        match addr {
+            DIV => self.div,
+            TIMA => self.tima,
+            TMA => self.tma,
+            // only the enable and clock select bits exist
+            TAC => self.tac | 0xF8,
+            _ => 0xFF,
         }
     }
 
-    pub fn get_registers(&mut self, memory: &Memory) {
-        self.div = memory.read(DIV);
-        self.tima = memory.read(TIMA);
-        self.tma = memory.read(TMA);
-        self.tac = memory.read(TAC);
+    pub fn write(&mut self, addr: u16, byte: u8) {
+        match addr {
+            // any write resets the divider
+            DIV => {
+                self.div = 0;
+                self.div_dots = 0;
+            }
+            TIMA => self.tima = byte,
+            TMA => self.tma = byte,
+            TAC => self.tac = byte,
+            _ => (),
+        }
     }
 
-    pub fn set_registers(&mut self, memory: &mut Memory) {
-        memory.write(DIV, self.div);
-        memory.write(TIMA, self.tima);
-        memory.write(TMA, self.tma);
-        memory.write(TAC, self.tac);
+    /// Sets DIV directly, writes through the bus can only reset it
+    pub fn set_div(&mut self, div: u8) {
+        self.div = div;
     }
 
-    pub fn update(&mut self, stopped: bool, memory: &mut Memory) {
-        self.get_registers(memory);
+    /// Returns true when TIMA overflowed and the timer interrupt should be requested
+    pub fn update(&mut self, stopped: bool) -> bool {
+        let mut interrupt = false;
         //println!("{}, {}, {}, {}", self.div, self.tima, self.tma, self.tac);
         if stopped {
             self.div = 0;
@@ -64,7 +79,7 @@ impl HTimer {
                 let sum = self.tima.overflowing_add(1);
                 if sum.1 {
                     self.tima = self.tma;
-                    memory.request_interrupt(TIMER_I);
+                    interrupt = true;
                 } else {
                     self.tima = sum.0;
                 }
@@ -75,6 +90,6 @@ impl HTimer {
         self.div_dots += 1;
         self.tima_dots += 1;
 
-        self.set_registers(memory);
+        interrupt
     }
 }
@@ -15,19 +15,105 @@ struct Args {
     #[arg(short='m', long)]
     log_memory: bool,
 
-    /// Path of rom to run
-    #[arg(short='p', long, default_value = "")]
-    rom_path: std::path::PathBuf,
+    /// Path of rom to run, can be a .zip, .gz or .7z archive
+    #[arg(short='p', long)]
+    rom_path: Option<std::path::PathBuf>,
+
+    /// Archive entry to run instead of the first .gb, .gbc or .gbx file
+    #[arg(short='e', long, requires = "rom_path")]
+    entry: Option<String>,
+
+    /// IPS, UPS or BPS patch to apply, repeat to stack them. Without it a patch
+    /// named after the rom is used
+    #[arg(long)]
+    patch: Vec<std::path::PathBuf>,
+
+    /// Game Genie or GameShark code, repeat for more. Added to the ones in the
+    /// rom's .cht file
+    #[arg(short='c', long, requires = "rom_path")]
+    cheat: Vec<String>,
+
+    /// No-Intro or Redump DAT file to look the rom up in
+    #[arg(short='d', long, requires = "rom_path")]
+    dat: Option<std::path::PathBuf>,
+
+    /// Boot rom to run first, defaults to ./gb_boot/DMG_ROM.gb when it exists
+    #[arg(short='b', long)]
+    boot_rom: Option<std::path::PathBuf>,
+
+    /// Start at 0x0100 with the registers the boot rom would leave
+    #[arg(short='s', long, conflicts_with = "boot_rom")]
+    skip_boot: bool,
+
+    /// Model to emulate the post boot state of (dmg, mgb, sgb, sgb2, cgb)
+    #[arg(long, default_value = "dmg")]
+    model: boot::Model,
+
+    /// What ram and registers hold at power on (zeros, ones, random, hardware)
+    #[arg(long, default_value = "zeros")]
+    power_on: power::PowerOnFill,
+
+    /// Seed for the random and hardware power on fills, printed when not given
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Stop with an error on an illegal opcode instead of locking up like hardware
+    #[arg(long)]
+    break_on_lockup: bool,
+
+    /// PNG or PGM pictures for the Pocket Camera, several are used one per capture
+    #[arg(long)]
+    camera_image: Vec<std::path::PathBuf>,
 }
 
 fn main() -> Result<(), String>{
     
     let args = Args::parse();
 
+    let boot_rom = match args.boot_rom {
+        Some(path) => Some(path),
+        None => Some(std::path::PathBuf::from(memory::BOOT_ROM_PATH)).filter(|path| path.exists()),
+    };
+
     let mut gb = GameBoy::new(args.log_memory);
-    gb.load_rom(args.rom_path);
+    gb.set_model(args.model);
+    let power_on = power::PowerOnState::new(args.power_on, args.seed);
+    if matches!(power_on.fill, power::PowerOnFill::Random | power::PowerOnFill::HardwareLike) {
+        println!("power on {} with seed {}", power_on.fill, power_on.seed);
+    }
+    gb.set_power_on(power_on);
+    if args.break_on_lockup {
+        gb.set_lockup_action(cpu::LockupAction::Break);
+    }
+    if !args.skip_boot {
+        gb.set_boot_rom(boot_rom.as_deref()).map_err(|e| format!("{e}, could not read boot rom"))?;
+    }
+    gb.set_patches(args.patch);
+    if let Some(rom_path) = args.rom_path {
+        gb.load_rom_entry(&rom_path, args.entry.as_deref()).map_err(|e| e.to_string())?;
+        for code in &args.cheat {
+            gb.add_cheat(code, "").map_err(|e| e.to_string())?;
+        }
+    }
+    if let Some(dat_path) = args.dat {
+        let dat = dat::Dat::load(&dat_path).map_err(|e| e.to_string())?;
+        match gb.identify(&dat) {
+            Some(entry) => {
+                let region = entry.region.as_deref().unwrap_or("unknown region");
+                println!("{} ({region}), {:?}", entry.name, entry.status);
+            }
+            None => println!("rom not found in {}", dat_path.display()),
+        }
+    }
+    if !args.camera_image.is_empty() {
+        let frames = mbc::sensor::FrameSequence::open(&args.camera_image).map_err(|e| e.to_string())?;
+        gb.set_image_source(Box::new(frames));
+    }
 
-    gb.run_emu().unwrap();
+    gb.run_emu()?;
+    if let Some(error) = gb.cpu_error() {
+        println!("cpu locked up, {error}");
+    }
 
 
     Ok(())
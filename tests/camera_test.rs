@@ -0,0 +1,131 @@
+mod common;
+
+use cassowary_gb::console::cartridge::*;
+use cassowary_gb::console::mbc::sensor::*;
+use cassowary_gb::console::memory::*;
+use common::*;
+
+use std::fs;
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cassowary-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// left half black, right half white
+fn half_and_half() -> Vec<u8> {
+    (0..SENSOR_SIZE).map(|i| if i % SENSOR_WIDTH < SENSOR_WIDTH / 2 { 0x00 } else { 0xFF }).collect()
+}
+
+fn camera() -> Memory {
+    let mut cartridge = Cartridge::new(make_rom(0xFC, 0x05, 0x04)).unwrap();
+    cartridge.set_image_source(Box::new(CallbackSource::new(half_and_half)));
+    let mut memory = Memory::new(8 * KBYTE);
+    memory.insert_cartridge(cartridge);
+    memory
+}
+
+fn set_registers(memory: &mut Memory, exposure: u16) {
+    memory.write(0x4000, 0x10);
+    memory.write(0xA002, (exposure >> 8) as u8);
+    memory.write(0xA003, exposure as u8);
+    for i in 0..16 {
+        memory.write(0xA006 + i * 3, 0x40);
+        memory.write(0xA007 + i * 3, 0x80);
+        memory.write(0xA008 + i * 3, 0xC0);
+    }
+}
+
+#[test]
+fn capture() {
+    let mut memory = camera();
+    memory.write(0x2000, 0x00);
+    assert_eq!(memory.read(0x4000), 0x00);
+
+    set_registers(&mut memory, 0x1000);
+    memory.write(0xA000, 0x01);
+    assert_eq!(memory.read(0xA000) & 0x01, 0x01);
+    // other registers are write only
+    assert_eq!(memory.read(0xA002), 0x00);
+
+    memory.write(0x4000, 0x00);
+    // tile 0 is all black, tile 8 starts the white half
+    assert_eq!(memory.read(0xA100), 0xFF);
+    assert_eq!(memory.read(0xA101), 0xFF);
+    assert_eq!(memory.read(0xA100 + 8 * 16), 0x00);
+    assert_eq!(memory.read(0xA101 + 8 * 16), 0x00);
+    // last row of the last tile
+    assert_eq!(memory.read(0xA100 + 223 * 16 + 14), 0x00);
+
+    memory.write(0x4000, 0x10);
+    for _ in 0..(32446 + 512 + 16 * 0x1000) * 4 {
+        memory.tick();
+    }
+    assert_eq!(memory.read(0xA000) & 0x01, 0x00);
+}
+
+#[test]
+fn exposure_and_invert() {
+    let mut memory = camera();
+    // half the exposure turns white into mid gray
+    set_registers(&mut memory, 0x0800);
+    memory.write(0xA000, 0x01);
+    memory.write(0x4000, 0x00);
+    assert_eq!(memory.read(0xA100 + 8 * 16), 0x00);
+    assert_eq!(memory.read(0xA101 + 8 * 16), 0xFF);
+
+    let mut memory = camera();
+    set_registers(&mut memory, 0x1000);
+    memory.write(0xA004, 0x08);
+    memory.write(0xA000, 0x01);
+    memory.write(0x4000, 0x00);
+    assert_eq!(memory.read(0xA100), 0x00);
+    assert_eq!(memory.read(0xA100 + 8 * 16), 0xFF);
+}
+
+#[test]
+fn image_files() {
+    let dir = temp_dir("camera");
+
+    let pgm = dir.join("image.pgm");
+    fs::write(&pgm, "P2\n# two by two\n2 2\n15\n0 15\n15 0\n").unwrap();
+    let frame = StillImage::open(&pgm).unwrap().frame();
+    assert_eq!(frame.len(), SENSOR_SIZE);
+    assert_eq!(frame[0], 0x00);
+    assert_eq!(frame[SENSOR_WIDTH - 1], 0xFF);
+    assert_eq!(frame[SENSOR_SIZE - 1], 0x00);
+
+    let raw = dir.join("raw.pgm");
+    fs::write(&raw, [b"P5 1 2 255\n".as_slice(), &[0x12, 0x34]].concat()).unwrap();
+    let frame = StillImage::open(&raw).unwrap().frame();
+    assert_eq!(frame[0], 0x12);
+    assert_eq!(frame[SENSOR_SIZE - 1], 0x34);
+
+    let png = dir.join("image.png");
+    let file = fs::File::create(&png).unwrap();
+    let mut encoder = png::Encoder::new(file, 2, 1);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&[0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00]).unwrap();
+    writer.finish().unwrap();
+
+    let mut frames = FrameSequence::open(&[&png, &pgm]).unwrap();
+    let frame = frames.frame();
+    assert_eq!(frame[0], 0xFF);
+    assert_eq!(frame[SENSOR_WIDTH - 1], 0x00);
+    assert_eq!(frames.frame()[0], 0x00);
+    assert_eq!(frames.frame()[0], 0xFF);
+
+    fs::write(dir.join("bad.pgm"), "not an image").unwrap();
+    assert!(matches!(StillImage::open(&dir.join("bad.pgm")), Err(ImageError::UnknownFormat)));
+    // sizes from the header can't be trusted
+    for header in ["P2 4294967296 4294967296 255\n0", "P2 100000 100000 255\n0", "P5 2 2 255\n\x00"] {
+        fs::write(dir.join("huge.pgm"), header).unwrap();
+        assert!(matches!(StillImage::open(&dir.join("huge.pgm")), Err(ImageError::Pgm(_))));
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use cassowary_gb::console::cartridge::*;
+use common::*;
+
+#[test]
+fn parse_header() {
+    let rom = make_rom(0x03, 0x01, 0x03);
+    let cartridge = Cartridge::new(rom).unwrap();
+    let header = &cartridge.header;
+    assert_eq!(header.title, "CASSOWARY");
+    assert_eq!(header.manufacturer_code, None);
+    assert_eq!(header.cgb, CgbSupport::None);
+    assert!(!header.sgb);
+    assert_eq!(header.licensee, 0x3031);
+    assert_eq!(header.cartridge_type.mapper, Mapper::Mbc1);
+    assert!(header.cartridge_type.ram && header.cartridge_type.battery);
+    assert_eq!(header.rom_size(), 64 * 1024);
+    assert_eq!(header.ram_size(), 32 * 1024);
+    assert_eq!(header.destination, Destination::Overseas);
+    assert_eq!(header.version, 0x02);
+    assert!(cartridge.warnings().is_empty());
+}
+
+#[test]
+fn parse_cgb_header() {
+    let mut rom = make_rom(0x19, 0x00, 0x00);
+    rom[0x013F..0x0143].copy_from_slice(b"ACSE");
+    rom[0x0143] = 0xC0;
+    fix_checksums(&mut rom);
+    let header = CartridgeHeader::parse(&rom).unwrap();
+    assert_eq!(header.cgb, CgbSupport::Only);
+    assert_eq!(header.manufacturer_code.as_deref(), Some("ACSE"));
+    assert_eq!(header.title, "CASSOWARY");
+    assert_eq!(header.cartridge_type.mapper, Mapper::Mbc5);
+}
+
+#[test]
+fn bad_headers() {
+    let mut rom = make_rom(0x00, 0x00, 0x00);
+    rom[0x014D] ^= 0xFF;
+    assert!(matches!(Cartridge::new(rom), Err(CartridgeError::HeaderChecksum { .. })));
+
+    let mut rom = make_rom(0x00, 0x00, 0x00);
+    rom[0x0147] = 0x04;
+    fix_checksums(&mut rom);
+    assert!(matches!(Cartridge::new(rom), Err(CartridgeError::UnknownCartridgeType(0x04))));
+
+    assert!(matches!(Cartridge::new(vec![0; 0x100]), Err(CartridgeError::TooSmall(0x100))));
+
+    let mut rom = make_rom(0x00, 0x00, 0x00);
+    rom[0x7000] = 0x12;
+    rom[0x0105] = 0x00;
+    let warnings = Cartridge::new(rom).unwrap().warnings();
+    assert!(warnings.contains(&HeaderWarning::LogoMismatch));
+    assert!(warnings.iter().any(|w| matches!(w, HeaderWarning::GlobalChecksum { .. })));
+}
+
+fn gbx_footer(id: &[u8; 4], flags: [bool; 3], rom_size: u32, ram_size: u32) -> Vec<u8> {
+    let mut footer = id.to_vec();
+    footer.extend(flags.map(|flag| flag as u8));
+    footer.push(0);
+    footer.extend(rom_size.to_be_bytes());
+    footer.extend(ram_size.to_be_bytes());
+    footer.extend([0; 32]);
+    footer.extend(0x40u32.to_be_bytes());
+    footer.extend(1u32.to_be_bytes());
+    footer.extend(0u32.to_be_bytes());
+    footer.extend(b"GBX!");
+    footer
+}
+
+#[test]
+fn gbx_footer_overrides_header() {
+    // header says rom only with an unknown type byte
+    let mut rom = make_rom(0x00, 0x02, 0x00);
+    rom[0x0147] = 0x42;
+    fix_checksums(&mut rom);
+    let len = rom.len();
+    assert!(matches!(Cartridge::new(rom.clone()), Err(CartridgeError::UnknownCartridgeType(0x42))));
+
+    rom.extend(gbx_footer(b"MBC5", [true, true, false], len as u32, 0x8000));
+    let mut cartridge = Cartridge::new(rom).unwrap();
+    let cartridge_type = cartridge.header.cartridge_type;
+    assert_eq!(cartridge_type.mapper, Mapper::Mbc5);
+    assert!(cartridge_type.ram && cartridge_type.battery && cartridge_type.rumble && !cartridge_type.timer);
+    assert_eq!(cartridge.header.ram_size(), 0x8000);
+    assert_eq!(cartridge.ram().len(), 0x8000);
+    assert_eq!(cartridge.rom().len(), len);
+    assert!(cartridge.warnings().is_empty());
+
+    cartridge.write_rom(0x2000, 0x05);
+    assert_eq!(cartridge.read_rom(0x4000), 0x05);
+
+    let cartridge = Cartridge::new(make_rom(0x00, 0x00, 0x00)).unwrap();
+    assert_eq!(cartridge.header.gbx, None);
+}
+
+#[test]
+fn gbx_multicart() {
+    // 1MiB MBC1, without the logo in every 256KiB it's banked like a normal cart
+    let rom = make_rom(0x01, 0x05, 0x00);
+    let mut cartridge = Cartridge::new(rom.clone()).unwrap();
+    cartridge.write_rom(0x2000, 0x11);
+    assert_eq!(cartridge.read_rom(0x4000), 0x11);
+
+    let mut gbx = rom.clone();
+    gbx.extend(gbx_footer(b"MB1M", [false; 3], rom.len() as u32, 0));
+    let mut cartridge = Cartridge::new(gbx).unwrap();
+    assert_eq!(cartridge.header.gbx.as_ref().map(|gbx| gbx.multicart), Some(true));
+    // 4 bit bank register
+    cartridge.write_rom(0x2000, 0x11);
+    assert_eq!(cartridge.read_rom(0x4000), 0x01);
+}
+
+#[test]
+fn bad_gbx_footers() {
+    let rom = make_rom(0x00, 0x00, 0x00);
+
+    let mut gbx = rom.clone();
+    gbx.extend(gbx_footer(b"XYZ\0", [false; 3], 0x8000, 0));
+    let result = Cartridge::new(gbx);
+    assert!(matches!(result, Err(CartridgeError::UnknownGbxMapper(id)) if id == "XYZ"));
+
+    let mut gbx = rom.clone();
+    gbx.extend(gbx_footer(b"ROM\0", [false; 3], 0x8000, 0));
+    let len = gbx.len();
+    gbx[len - 12..len - 8].copy_from_slice(&2u32.to_be_bytes());
+    let result = Cartridge::new(gbx);
+    assert!(matches!(result, Err(CartridgeError::GbxVersion { major: 2, minor: 0 })));
+
+    let mut gbx = rom;
+    gbx.extend(gbx_footer(b"ROM\0", [false; 3], 0x8000, 0));
+    let len = gbx.len();
+    gbx[len - 16..len - 12].copy_from_slice(&0x20u32.to_be_bytes());
+    assert!(matches!(Cartridge::new(gbx), Err(CartridgeError::GbxFooterSize(0x20))));
+
+    // sizes no cartridge has
+    let mut gbx = make_rom(0x00, 0x00, 0x00);
+    gbx.extend(gbx_footer(b"MBC5", [true; 3], 0x8000, u32::MAX));
+    assert!(matches!(Cartridge::new(gbx), Err(CartridgeError::GbxRamSize(0xFFFF_FFFF))));
+    let mut gbx = make_rom(0x00, 0x00, 0x00);
+    gbx.extend(gbx_footer(b"MBC5", [true; 3], 0x0100_0000, 0x2000));
+    assert!(matches!(Cartridge::new(gbx), Err(CartridgeError::GbxRomSize(0x0100_0000))));
+}
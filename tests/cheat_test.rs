@@ -0,0 +1,127 @@
+mod common;
+
+use cassowary_gb::console::cartridge::*;
+use cassowary_gb::console::cheat::*;
+use cassowary_gb::console::loader::*;
+use cassowary_gb::console::memory::*;
+use cassowary_gb::console::*;
+use common::*;
+
+use std::fs;
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cassowary-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn memory_with(rom: Vec<u8>) -> Memory {
+    let mut memory = Memory::new(8 * KBYTE);
+    memory.insert_cartridge(Cartridge::new(rom).unwrap());
+    memory
+}
+
+#[test]
+fn parse_codes() {
+    let cheat = Cheat::new("3e1-4fa", "lives").unwrap();
+    assert_eq!(cheat.code, "3E1-4FA");
+    assert_eq!(cheat.kind, CheatKind::GameGenie { address: 0x514F, value: 0x3E, compare: None });
+
+    let cheat = Cheat::new("3E1-4FA-2A9", "").unwrap();
+    assert_eq!(cheat.kind, CheatKind::GameGenie { address: 0x514F, value: 0x3E, compare: Some(0xF0) });
+
+    let cheat = Cheat::new("010238CD", "").unwrap();
+    assert_eq!(cheat.kind, CheatKind::GameShark { bank: SharkBank::Current, address: 0xCD38, value: 0x02 });
+    let cheat = Cheat::new("8263FFA0", "").unwrap();
+    assert_eq!(cheat.kind, CheatKind::GameShark { bank: SharkBank::Ram(2), address: 0xA0FF, value: 0x63 });
+    let cheat = Cheat::new("910500D0", "").unwrap();
+    assert_eq!(cheat.kind, CheatKind::GameShark { bank: SharkBank::Wram(1), address: 0xD000, value: 0x05 });
+
+    for code in ["", "3E1-4F", "3E1-4FA-2A", "XYZ-123", "420238CD", "000-007", "01010020"] {
+        assert!(matches!(Cheat::new(code, ""), Err(CheatError::InvalidCode(_, None))), "{code}");
+    }
+}
+
+#[test]
+fn game_genie_reads() {
+    // MBC1, bank n starts with n
+    let mut memory = memory_with(make_rom(0x01, 0x02, 0x00));
+    memory.cheats_mut().add("990-00B", "").unwrap();
+    assert_eq!(memory.read(0x4000), 0x99);
+    assert_eq!(memory.read(0x4001), 0x00);
+
+    // only while bank 1 is mapped
+    let mut memory = memory_with(make_rom(0x01, 0x02, 0x00));
+    memory.cheats_mut().add("990-00B-E6E", "").unwrap();
+    assert_eq!(memory.read(0x4000), 0x99);
+    memory.write(0x2000, 0x02);
+    assert_eq!(memory.read(0x4000), 0x02);
+    memory.write(0x2000, 0x01);
+
+    memory.cheats_mut().set_enabled(0, false);
+    assert_eq!(memory.read(0x4000), 0x01);
+    memory.cheats_mut().set_enabled(0, true);
+    assert_eq!(memory.read(0x4000), 0x99);
+    assert!(memory.cheats_mut().remove(0).is_some());
+    assert_eq!(memory.read(0x4000), 0x01);
+    assert!(!memory.cheats_mut().set_enabled(0, true));
+}
+
+#[test]
+fn gameshark_writes() {
+    // MBC5 with 4 ram banks
+    let mut memory = memory_with(make_rom(0x1B, 0x02, 0x03));
+    memory.cheats_mut().add("014200C1", "").unwrap();
+    memory.cheats_mut().add("8263FFA0", "").unwrap();
+    memory.cheats_mut().add("930700D0", "").unwrap();
+
+    memory.apply_cheats();
+    assert_eq!(memory.read(0xC100), 0x42);
+    // no DMG work ram bank 3
+    assert_eq!(memory.read(0xD000), 0x00);
+    memory.write(0xC100, 0x00);
+    memory.apply_cheats();
+    assert_eq!(memory.read(0xC100), 0x42);
+
+    // the banked code lands in bank 2 even while bank 0 is mapped
+    memory.write(0x0000, 0x0A);
+    assert_eq!(memory.read(0xA0FF), 0x00);
+    memory.write(0x4000, 0x02);
+    assert_eq!(memory.read(0xA0FF), 0x63);
+}
+
+#[test]
+fn cheat_file_per_rom() {
+    let dir = temp_dir("cheats");
+    let rom_path = dir.join("game.gb");
+    fs::write(&rom_path, make_rom(0x00, 0x00, 0x00)).unwrap();
+    fs::write(
+        dir.join("game.cht"),
+        "# infinite things\n01420AC1 Health\n\n-01430BC1 Off for now\n990-00B Rom byte\n",
+    )
+    .unwrap();
+
+    let mut gb = GameBoy::new(false);
+    gb.load_rom(rom_path.clone()).unwrap();
+    let cheats = gb.cheats();
+    assert_eq!(cheats.len(), 3);
+    assert_eq!(cheats[0].name, "Health");
+    assert!(!cheats[1].enabled);
+    assert_eq!(gb.gamepack.lock().unwrap().read(0x4000), 0x99);
+
+    assert!(gb.set_cheat_enabled(2, false));
+    assert_eq!(gb.gamepack.lock().unwrap().read(0x4000), 0x01);
+    assert_eq!(gb.add_cheat("01440CC1", "").unwrap(), 3);
+    gb.gamepack.lock().unwrap().apply_cheats();
+    let memory = gb.gamepack.lock().unwrap();
+    assert_eq!(memory.read(0xC10A), 0x42);
+    assert_eq!(memory.read(0xC10B), 0x00);
+    assert_eq!(memory.read(0xC10C), 0x44);
+    drop(memory);
+
+    fs::write(dir.join("game.cht"), "01420AC1\nnot-a-code\n").unwrap();
+    let mut gb = GameBoy::new(false);
+    let result = gb.load_rom(rom_path);
+    assert!(matches!(result, Err(LoadError::Cheat(CheatError::InvalidCode(_, Some(2))))));
+}
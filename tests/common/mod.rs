@@ -0,0 +1,30 @@
+#![allow(dead_code)]
+
+use cassowary_gb::console::cartridge::*;
+
+/// Builds a rom with a valid header, every bank starts with its own bank number
+pub fn make_rom(cartridge_type: u8, rom_size_code: u8, ram_size_code: u8) -> Vec<u8> {
+    let mut rom = vec![0x00; rom_size_from_code(rom_size_code).unwrap()];
+    for bank in 1..rom.len() / ROM_BANK_SIZE {
+        rom[bank * ROM_BANK_SIZE] = bank as u8;
+        rom[bank * ROM_BANK_SIZE + 1] = (bank >> 8) as u8;
+    }
+    rom[0x0104..0x0134].copy_from_slice(&NINTENDO_LOGO);
+    rom[0x0134..0x013D].copy_from_slice(b"CASSOWARY");
+    rom[0x0147] = cartridge_type;
+    rom[0x0148] = rom_size_code;
+    rom[0x0149] = ram_size_code;
+    rom[0x014A] = 0x01;
+    rom[0x014B] = 0x33;
+    rom[0x0144] = b'0';
+    rom[0x0145] = b'1';
+    rom[0x014C] = 0x02;
+    fix_checksums(&mut rom);
+    rom
+}
+
+pub fn fix_checksums(rom: &mut [u8]) {
+    rom[0x014D] = CartridgeHeader::compute_header_checksum(rom);
+    let global = CartridgeHeader::compute_global_checksum(rom);
+    rom[0x014E..0x0150].copy_from_slice(&global.to_be_bytes());
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use cassowary_gb::console::dat::*;
+use cassowary_gb::console::*;
+use common::*;
+
+use std::fs;
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cassowary-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn dat_xml(games: &str) -> String {
+    format!(
+        r#"<?xml version="1.0"?>
+<!DOCTYPE datafile PUBLIC "-//Logiqx//DTD ROM Management Datafile//EN" "http://www.logiqx.com/dtds/datafile.dtd">
+<datafile>
+    <header>
+        <name>Nintendo - Game Boy</name>
+    </header>
+{games}
+</datafile>"#
+    )
+}
+
+#[test]
+fn rom_identity() {
+    let identity = RomIdentity::of(b"123456789");
+    assert_eq!(identity.crc32, 0xCBF43926);
+    assert_eq!(identity.sha1, "f7c3bc1d808e04732adf679965ccc34ca7ae3441");
+    assert_eq!(identity.size, 9);
+}
+
+#[test]
+fn parse_and_identify() {
+    let a = RomIdentity::of(b"rom a");
+    let b = RomIdentity::of(b"rom b");
+    let c = RomIdentity::of(b"rom c");
+    let games = format!(
+        r#"    <game name="Cassowary (World) (Rev 1)">
+        <description>Cassowary (World) (Rev 1)</description>
+        <rom name="Cassowary (World) (Rev 1).gb" size="5" crc="{:08X}" sha1="{}" status="verified"/>
+    </game>
+    <game name="Cassowary (Japan) [b]">
+        <rom name="Cassowary (Japan) [b].gb" size="5" crc="{:08x}" status="baddump"/>
+    </game>
+    <game name="Emu">
+        <release name="Emu" region="EUR"/>
+        <rom name="Emu.gb" size="5" sha1="{}"/>
+    </game>"#,
+        a.crc32,
+        a.sha1.to_uppercase(),
+        b.crc32,
+        c.sha1,
+    );
+    let dat = Dat::parse(&dat_xml(&games)).unwrap();
+    assert_eq!(dat.name.as_deref(), Some("Nintendo - Game Boy"));
+    assert_eq!(dat.entries.len(), 3);
+
+    let entry = dat.identify(&a).unwrap();
+    assert_eq!(entry.name, "Cassowary (World) (Rev 1)");
+    assert_eq!(entry.region.as_deref(), Some("World"));
+    assert_eq!(entry.status, DumpStatus::Verified);
+
+    let entry = dat.identify(&b).unwrap();
+    assert_eq!(entry.region.as_deref(), Some("Japan"));
+    assert_eq!(entry.status, DumpStatus::BadDump);
+
+    let entry = dat.identify(&c).unwrap();
+    assert_eq!(entry.region.as_deref(), Some("EUR"));
+    assert_eq!(entry.status, DumpStatus::Good);
+
+    assert_eq!(dat.identify(&RomIdentity::of(b"rom d")), None);
+    // right crc, wrong size
+    let mut wrong_size = b.clone();
+    wrong_size.size = 6;
+    assert_eq!(dat.identify(&wrong_size), None);
+
+    assert!(matches!(Dat::parse("<datafile><game>"), Err(DatError::Xml(_))));
+}
+
+#[test]
+fn identify_loaded_rom() {
+    let dir = temp_dir("dat");
+    let rom = make_rom(0x00, 0x00, 0x00);
+    let rom_path = dir.join("game.gb");
+    fs::write(&rom_path, &rom).unwrap();
+    let identity = RomIdentity::of(&rom);
+    let dat_path = dir.join("gb.dat");
+    let games = format!(
+        r#"<game name="Cassowary (USA, Europe)"><rom name="Cassowary (USA, Europe).gb" size="{}" crc="{:08X}"/></game>"#,
+        rom.len(),
+        identity.crc32,
+    );
+    fs::write(&dat_path, dat_xml(&games)).unwrap();
+
+    let mut gb = GameBoy::new(false);
+    gb.load_rom(rom_path.clone()).unwrap();
+    assert_eq!(gb.identity(), Some(&identity));
+    let dat = Dat::load(&dat_path).unwrap();
+    let entry = gb.identify(&dat).unwrap();
+    assert_eq!(entry.name, "Cassowary (USA, Europe)");
+    assert_eq!(entry.region.as_deref(), Some("USA, Europe"));
+
+    // a translation patch still names the dump it was made for
+    fs::write(dir.join("game.ips"), b"PATCH\x00\x02\x00\x00\x01\x24EOF").unwrap();
+    let mut gb = GameBoy::new(false);
+    gb.load_rom(rom_path).unwrap();
+    assert_eq!(gb.gamepack.lock().unwrap().read(0x0200), 0x24);
+    assert_eq!(gb.identity(), Some(&identity));
+
+    assert!(matches!(Dat::load(&dir.join("missing.dat")), Err(DatError::Io(_))));
+}
@@ -189,3 +189,11 @@ fn decode_cb() {
     }
 }
 
+
+#[test]
+fn decode_tables() {
+    for opcode in 0..=u8::MAX {
+        assert_eq!(Instruction::decode(opcode), Instruction::decode_fields(opcode));
+        assert_eq!(Instruction::decode_cb(opcode), Instruction::decode_cb_fields(opcode));
+    }
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use cassowary_gb::console::loader::*;
+use cassowary_gb::console::*;
+use common::*;
+
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use flate2::write::GzEncoder;
+use zip::write::FileOptions;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cassowary-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+    let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+    for (name, data) in entries {
+        zip.start_file(*name, FileOptions::default()).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+#[test]
+fn zip_archives() {
+    let dir = temp_dir("loader-zip");
+    let rom = make_rom(0x00, 0x00, 0x00);
+    let other = make_rom(0x01, 0x00, 0x00);
+    let archive = dir.join("pack.zip");
+    write_zip(
+        &archive,
+        &[("readme.txt", b"not a rom"), ("roms/Game.GB", &rom), ("other.gbc", &other)],
+    );
+
+    // first rom entry when no name is given, saves sit next to the archive
+    let file = RomFile::open(&archive, None).unwrap();
+    assert_eq!(file.data, rom);
+    assert_eq!(file.path, dir.join("Game.GB"));
+
+    let file = RomFile::open(&archive, Some("other.gbc")).unwrap();
+    assert_eq!(file.data, other);
+    let file = RomFile::open(&archive, Some("roms/Game.GB")).unwrap();
+    assert_eq!(file.data, rom);
+
+    assert!(matches!(RomFile::open(&archive, Some("missing.gb")), Err(LoadError::EntryNotFound(_))));
+
+    let empty = dir.join("empty.zip");
+    write_zip(&empty, &[("readme.txt", b"not a rom")]);
+    assert!(matches!(RomFile::open(&empty, None), Err(LoadError::NoRom)));
+}
+
+#[test]
+fn gzip_and_plain_files() {
+    let dir = temp_dir("loader-gz");
+    let rom = make_rom(0x00, 0x00, 0x00);
+
+    let archive = dir.join("game.gb.gz");
+    let mut encoder = GzEncoder::new(File::create(&archive).unwrap(), flate2::Compression::default());
+    encoder.write_all(&rom).unwrap();
+    encoder.finish().unwrap();
+    let file = RomFile::open(&archive, None).unwrap();
+    assert_eq!(file.data, rom);
+    assert_eq!(file.path, dir.join("game.gb"));
+
+    let plain = dir.join("plain.gb");
+    fs::write(&plain, &rom).unwrap();
+    let file = RomFile::open(&plain, None).unwrap();
+    assert_eq!(file.data, rom);
+    assert_eq!(file.path, plain);
+
+    assert!(matches!(RomFile::open(&dir.join("missing.gb"), None), Err(LoadError::Io(_))));
+
+    // a few KiB that unpack past any real cartridge
+    let bomb = dir.join("bomb.gb.gz");
+    let mut encoder = GzEncoder::new(File::create(&bomb).unwrap(), flate2::Compression::best());
+    encoder.write_all(&vec![0; 16 * 1024 * 1024]).unwrap();
+    encoder.finish().unwrap();
+    assert!(matches!(RomFile::open(&bomb, None), Err(LoadError::TooLarge)));
+}
+
+#[test]
+fn seven_zip_archives() {
+    let dir = temp_dir("loader-7z");
+    let contents = dir.join("contents");
+    fs::create_dir_all(&contents).unwrap();
+    let rom = make_rom(0x00, 0x00, 0x00);
+    fs::write(contents.join("a.txt"), b"not a rom").unwrap();
+    fs::write(contents.join("game.gbc"), &rom).unwrap();
+
+    let archive = dir.join("pack.7z");
+    sevenz_rust::compress_to_path(&contents, &archive).unwrap();
+    let file = RomFile::open(&archive, None).unwrap();
+    assert_eq!(file.data, rom);
+    assert_eq!(file.path, dir.join("game.gbc"));
+}
+
+#[test]
+fn battery_save_named_after_the_packed_rom() {
+    let dir = temp_dir("loader-save");
+    let archive = dir.join("pack.zip");
+    write_zip(&archive, &[("game.gb", &make_rom(0x1B, 0x02, 0x02))]);
+
+    let mut gb = GameBoy::new(false);
+    gb.load_rom(archive.clone()).unwrap();
+    {
+        let mut memory = gb.gamepack.lock().unwrap();
+        memory.write(0x0000, 0x0A);
+        memory.write(0xA000, 0x12);
+    }
+    gb.flush_save().unwrap();
+    assert_eq!(fs::read(dir.join("game.sav")).unwrap()[0], 0x12);
+    assert!(!dir.join("pack.sav").exists());
+}
+
+#[test]
+fn load_errors_are_returned() {
+    let dir = temp_dir("loader-errors");
+    let archive = dir.join("pack.zip");
+    write_zip(&archive, &[("game.gb", &[0u8; 0x10])]);
+
+    let mut gb = GameBoy::new(false);
+    assert!(matches!(gb.load_rom(dir.join("missing.gb")), Err(LoadError::Io(_))));
+    assert!(matches!(gb.load_rom(archive), Err(LoadError::Cartridge(_))));
+}
@@ -0,0 +1,483 @@
+mod common;
+
+use cassowary_gb::console::cartridge::*;
+use cassowary_gb::console::mbc::rtc::*;
+use cassowary_gb::console::mbc::*;
+use cassowary_gb::console::memory::*;
+use common::*;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+fn memory_with(rom: Vec<u8>) -> Memory {
+    let mut memory = Memory::new(8 * KBYTE);
+    memory.insert_cartridge(Cartridge::new(rom).unwrap());
+    memory
+}
+
+#[test]
+fn rom_only() {
+    let mut memory = memory_with(make_rom(0x00, 0x00, 0x00));
+    assert_eq!(memory.read(0x4000), 0x01);
+    memory.write(0x2000, 0x05);
+    memory.write(0x0150, 0x42);
+    assert_eq!(memory.read(0x4000), 0x01);
+    assert_eq!(memory.read(0x0150), 0x00);
+    assert_eq!(memory.read(0xA000), 0xFF);
+}
+
+#[test]
+fn mbc1_rom_banking() {
+    // 2MiB, 128 banks
+    let mut memory = memory_with(make_rom(0x01, 0x06, 0x00));
+    assert_eq!(memory.read(0x4000), 0x01);
+
+    // bank 0 maps to bank 1
+    memory.write(0x2000, 0x00);
+    assert_eq!(memory.read(0x4000), 0x01);
+    memory.write(0x2000, 0x1F);
+    assert_eq!(memory.read(0x4000), 0x1F);
+
+    // upper bits, 0x20 also can't be selected through 0x4000-0x7FFF
+    memory.write(0x4000, 0x01);
+    memory.write(0x2000, 0x00);
+    assert_eq!(memory.read(0x4000), 0x21);
+    memory.write(0x2000, 0x05);
+    assert_eq!(memory.read(0x4000), 0x25);
+    assert_eq!(memory.read(0x0000), 0x00);
+
+    // mode 1 also switches the 0x0000-0x3FFF area
+    memory.write(0x6000, 0x01);
+    assert_eq!(memory.read(0x0000), 0x20);
+    memory.write(0x6000, 0x00);
+    assert_eq!(memory.read(0x0000), 0x00);
+
+    // the bank number is masked to the rom size
+    let mut memory = memory_with(make_rom(0x01, 0x02, 0x00));
+    memory.write(0x2000, 0x09);
+    assert_eq!(memory.read(0x4000), 0x01);
+}
+
+#[test]
+fn mbc1_ram_banking() {
+    let mut memory = memory_with(make_rom(0x03, 0x01, 0x03));
+
+    // disabled by default
+    memory.write(0xA000, 0x12);
+    assert_eq!(memory.read(0xA000), 0xFF);
+
+    memory.write(0x0000, 0x0A);
+    memory.write(0xA000, 0x12);
+    assert_eq!(memory.read(0xA000), 0x12);
+
+    // ram banks only switch in mode 1
+    memory.write(0x4000, 0x02);
+    assert_eq!(memory.read(0xA000), 0x12);
+    memory.write(0x6000, 0x01);
+    assert_eq!(memory.read(0xA000), 0x00);
+    memory.write(0xA000, 0x34);
+    memory.write(0x4000, 0x00);
+    assert_eq!(memory.read(0xA000), 0x12);
+    memory.write(0x4000, 0x02);
+    assert_eq!(memory.read(0xA000), 0x34);
+
+    memory.write(0x0000, 0x00);
+    assert_eq!(memory.read(0xA000), 0xFF);
+}
+
+#[test]
+fn mbc1_multicart() {
+    let mut rom = make_rom(0x01, 0x05, 0x00);
+    rom[0x40104..0x40134].copy_from_slice(&NINTENDO_LOGO);
+    assert!(Mbc1::is_multicart(&rom));
+    let mut memory = memory_with(rom);
+
+    // bank2 selects 256KiB games
+    memory.write(0x4000, 0x01);
+    memory.write(0x2000, 0x03);
+    assert_eq!(memory.read(0x4000), 0x13);
+    // bit 4 of bank1 is not wired
+    memory.write(0x2000, 0x13);
+    assert_eq!(memory.read(0x4000), 0x13);
+    // bank 0x10 is still remapped to bank1 = 0x10, which is bank 0 of the game
+    memory.write(0x2000, 0x10);
+    assert_eq!(memory.read(0x4000), 0x10);
+    memory.write(0x6000, 0x01);
+    assert_eq!(memory.read(0x0000), 0x10);
+}
+
+#[derive(Debug, Clone, Default)]
+struct FakeClock(Arc<AtomicU64>);
+
+impl Clock for FakeClock {
+    fn now(&self) -> u64 {
+        self.0.load(Ordering::SeqCst)
+    }
+}
+
+impl FakeClock {
+    fn advance(&self, seconds: u64) {
+        self.0.fetch_add(seconds, Ordering::SeqCst);
+    }
+}
+
+fn mbc3_with_clock(clock: &FakeClock) -> Memory {
+    let rom = make_rom(0x10, 0x06, 0x03);
+    let header = CartridgeHeader::parse(&rom).unwrap();
+    let mbc = Mbc3::new(Some(Rtc::new(Box::new(clock.clone()))));
+    let mut memory = Memory::new(8 * KBYTE);
+    memory.insert_cartridge(Cartridge::with_mbc(header, rom, Box::new(mbc)));
+    memory
+}
+
+fn read_rtc(memory: &mut Memory, register: u8) -> u8 {
+    memory.write(0x4000, register);
+    memory.read(0xA000)
+}
+
+fn latch(memory: &mut Memory) {
+    memory.write(0x6000, 0x00);
+    memory.write(0x6000, 0x01);
+}
+
+#[test]
+fn mbc3_banking() {
+    let clock = FakeClock::default();
+    let mut memory = mbc3_with_clock(&clock);
+    memory.write(0x2000, 0x00);
+    assert_eq!(memory.read(0x4000), 0x01);
+    memory.write(0x2000, 0x7F);
+    assert_eq!(memory.read(0x4000), 0x7F);
+
+    memory.write(0x0000, 0x0A);
+    for bank in 0..4 {
+        memory.write(0x4000, bank);
+        memory.write(0xA123, bank + 0x10);
+    }
+    for bank in 0..4 {
+        memory.write(0x4000, bank);
+        assert_eq!(memory.read(0xA123), bank + 0x10);
+    }
+}
+
+#[test]
+fn mbc3_rtc() {
+    let clock = FakeClock::default();
+    let mut memory = mbc3_with_clock(&clock);
+    memory.write(0x0000, 0x0A);
+
+    clock.advance(59 + 60 * 2 + 3600 * 3 + 86400 * 260);
+    // registers don't move until latched
+    assert_eq!(read_rtc(&mut memory, 0x08), 0);
+    latch(&mut memory);
+    assert_eq!(read_rtc(&mut memory, 0x08), 59);
+    assert_eq!(read_rtc(&mut memory, 0x09), 2);
+    assert_eq!(read_rtc(&mut memory, 0x0A), 3);
+    assert_eq!(read_rtc(&mut memory, 0x0B), 4);
+    assert_eq!(read_rtc(&mut memory, 0x0C), 0x01);
+
+    // latching needs 0x00 before 0x01
+    clock.advance(1);
+    memory.write(0x6000, 0x01);
+    assert_eq!(read_rtc(&mut memory, 0x08), 59);
+    latch(&mut memory);
+    assert_eq!(read_rtc(&mut memory, 0x08), 0);
+    assert_eq!(read_rtc(&mut memory, 0x09), 3);
+
+    // day counter overflow sets the carry bit
+    clock.advance(86400 * 252);
+    latch(&mut memory);
+    assert_eq!(read_rtc(&mut memory, 0x0B), 0);
+    assert_eq!(read_rtc(&mut memory, 0x0C), 0x80);
+
+    // halt stops the clock, writes set the time
+    memory.write(0x4000, 0x0C);
+    memory.write(0xA000, 0x40);
+    memory.write(0x4000, 0x08);
+    memory.write(0xA000, 30);
+    clock.advance(100);
+    latch(&mut memory);
+    assert_eq!(read_rtc(&mut memory, 0x08), 30);
+    assert_eq!(read_rtc(&mut memory, 0x0C), 0x40);
+    memory.write(0x4000, 0x0C);
+    memory.write(0xA000, 0x00);
+    clock.advance(45);
+    latch(&mut memory);
+    assert_eq!(read_rtc(&mut memory, 0x08), 15);
+    assert_eq!(read_rtc(&mut memory, 0x09), 4);
+
+    // disabled ram/timer reads open bus
+    memory.write(0x0000, 0x00);
+    assert_eq!(read_rtc(&mut memory, 0x08), 0xFF);
+}
+
+#[test]
+fn mbc5_banking() {
+    // 8MiB, 512 banks
+    let mut memory = memory_with(make_rom(0x1B, 0x08, 0x04));
+    memory.write(0x2000, 0x00);
+    assert_eq!(memory.read(0x4000), 0x00);
+    memory.write(0x2000, 0x34);
+    memory.write(0x3000, 0x01);
+    assert_eq!(memory.read(0x4000), 0x34);
+    assert_eq!(memory.read(0x4001), 0x01);
+    memory.write(0x3000, 0x00);
+    assert_eq!(memory.read(0x4000), 0x34);
+    assert_eq!(memory.read(0x4001), 0x00);
+
+    memory.write(0x0000, 0x0A);
+    for bank in 0..16 {
+        memory.write(0x4000, bank);
+        memory.write(0xB000, bank + 0x20);
+    }
+    for bank in 0..16 {
+        memory.write(0x4000, bank);
+        assert_eq!(memory.read(0xB000), bank + 0x20);
+    }
+}
+
+#[test]
+fn mbc5_rumble() {
+    let mut memory = memory_with(make_rom(0x1E, 0x02, 0x03));
+    memory.write(0x0000, 0x0A);
+    memory.write(0x4000, 0x01);
+    memory.write(0xA000, 0x11);
+
+    memory.write(0x4000, 0x09);
+    assert!(memory.cartridge().unwrap().rumble());
+    // bit 3 is the motor, not a ram bank
+    assert_eq!(memory.read(0xA000), 0x11);
+    memory.write(0x4000, 0x08);
+    memory.write(0x4000, 0x01);
+    assert!(!memory.cartridge().unwrap().rumble());
+
+    let events = memory.cartridge_mut().unwrap().take_events();
+    assert_eq!(events, vec![CartridgeEvent::Rumble(true), CartridgeEvent::Rumble(false)]);
+    assert!(memory.cartridge_mut().unwrap().take_events().is_empty());
+}
+
+#[test]
+fn mbc2() {
+    let mut memory = memory_with(make_rom(0x06, 0x03, 0x00));
+    assert_eq!(memory.cartridge().unwrap().ram().len(), 512);
+
+    // address bit 8 set selects the rom bank
+    memory.write(0x2100, 0x05);
+    assert_eq!(memory.read(0x4000), 0x05);
+    memory.write(0x0100, 0x00);
+    assert_eq!(memory.read(0x4000), 0x01);
+    memory.write(0x3FFF, 0x0F);
+    assert_eq!(memory.read(0x4000), 0x0F);
+    memory.write(0x4000, 0x03);
+    assert_eq!(memory.read(0x4000), 0x0F);
+
+    // bit 8 clear enables ram, doesn't touch the rom bank
+    memory.write(0xA000, 0x05);
+    assert_eq!(memory.read(0xA000), 0xFF);
+    memory.write(0x00FF, 0x0A);
+    assert_eq!(memory.read(0x4000), 0x0F);
+    memory.write(0xA000, 0x35);
+    assert_eq!(memory.read(0xA000), 0xF5);
+    // echoed every 512 bytes
+    assert_eq!(memory.read(0xA200), 0xF5);
+    assert_eq!(memory.read(0xBE00), 0xF5);
+    memory.write(0xB1FF, 0x0C);
+    assert_eq!(memory.read(0xA1FF), 0xFC);
+}
+
+#[test]
+fn mbc3_rtc_save() {
+    let clock = FakeClock::default();
+    let mut memory = mbc3_with_clock(&clock);
+    memory.write(0x0000, 0x0A);
+    memory.write(0x4000, 0x00);
+    memory.write(0xA000, 0x56);
+    clock.advance(3661);
+
+    let save = memory.cartridge().unwrap().save_data();
+    assert_eq!(save.len(), 0x8000 + 48);
+    assert_eq!(save[0], 0x56);
+
+    // two hours pass while the game is off
+    clock.advance(7200);
+    let mut memory = mbc3_with_clock(&clock);
+    memory.cartridge_mut().unwrap().load_save_data(&save);
+    memory.write(0x0000, 0x0A);
+    latch(&mut memory);
+    assert_eq!(read_rtc(&mut memory, 0x08), 1);
+    assert_eq!(read_rtc(&mut memory, 0x09), 1);
+    assert_eq!(read_rtc(&mut memory, 0x0A), 3);
+    memory.write(0x4000, 0x00);
+    assert_eq!(memory.read(0xA000), 0x56);
+}
+
+#[test]
+fn huc1() {
+    let mut memory = memory_with(make_rom(0xFF, 0x05, 0x03));
+    memory.write(0x2000, 0x3F);
+    assert_eq!(memory.read(0x4000), 0x3F);
+    memory.write(0x2000, 0x05);
+    assert_eq!(memory.read(0x4000), 0x05);
+
+    memory.write(0x4000, 0x02);
+    memory.write(0xA000, 0x12);
+    memory.write(0x4000, 0x00);
+    assert_ne!(memory.read(0xA000), 0x12);
+    memory.write(0x4000, 0x02);
+    assert_eq!(memory.read(0xA000), 0x12);
+
+    // ir mode, bit 0 reads low when light comes in
+    memory.write(0x0000, 0x0E);
+    assert_eq!(memory.read(0xA000), 0xC1);
+    memory.cartridge_mut().unwrap().set_infrared(true);
+    assert_eq!(memory.read(0xA000), 0xC0);
+    memory.write(0xA000, 0x01);
+    memory.write(0xA000, 0x01);
+    memory.write(0xA000, 0x00);
+    let events = memory.cartridge_mut().unwrap().take_events();
+    assert_eq!(events, vec![CartridgeEvent::Infrared(true), CartridgeEvent::Infrared(false)]);
+
+    memory.write(0x0000, 0x0A);
+    assert_eq!(memory.read(0xA000), 0x12);
+}
+
+fn huc3_with_clock(clock: &FakeClock) -> Memory {
+    let rom = make_rom(0xFE, 0x06, 0x03);
+    let header = CartridgeHeader::parse(&rom).unwrap();
+    let mbc = HuC3::new(Box::new(clock.clone()));
+    let mut memory = Memory::new(8 * KBYTE);
+    memory.insert_cartridge(Cartridge::with_mbc(header, rom, Box::new(mbc)));
+    memory
+}
+
+fn huc3_command(memory: &mut Memory, command: u8) -> u8 {
+    memory.write(0x0000, 0x0B);
+    memory.write(0xA000, command);
+    memory.write(0x0000, 0x0C);
+    memory.read(0xA000)
+}
+
+#[test]
+fn huc3() {
+    let clock = FakeClock::default();
+    let mut memory = huc3_with_clock(&clock);
+    memory.write(0x2000, 0x45);
+    assert_eq!(memory.read(0x4000), 0x45);
+
+    // ram is only writable in mode 0x0A
+    memory.write(0x0000, 0x0A);
+    memory.write(0xA000, 0x34);
+    memory.write(0x0000, 0x00);
+    memory.write(0xA000, 0x56);
+    assert_eq!(memory.read(0xA000), 0x34);
+
+    // 2 days, 3 hours and 4 minutes
+    clock.advance(2 * 86400 + 3 * 3600 + 4 * 60 + 30);
+    huc3_command(&mut memory, 0x60);
+    huc3_command(&mut memory, 0x40);
+    huc3_command(&mut memory, 0x50);
+    let minutes = (0..3).fold(0u16, |n, i| n | ((huc3_command(&mut memory, 0x10) & 0x0F) as u16) << (i * 4));
+    let days = (0..3).fold(0u16, |n, i| n | ((huc3_command(&mut memory, 0x10) & 0x0F) as u16) << (i * 4));
+    assert_eq!(minutes, 3 * 60 + 4);
+    assert_eq!(days, 2);
+    assert_eq!(huc3_command(&mut memory, 0x62), 0x80 | 0x61);
+
+    // set the clock to day 0x123, minute 0
+    huc3_command(&mut memory, 0x40);
+    for nibble in [0x0, 0x0, 0x0, 0x3, 0x2, 0x1] {
+        huc3_command(&mut memory, 0x30 | nibble);
+    }
+    huc3_command(&mut memory, 0x61);
+    // the seconds into the minute are saved too
+    clock.advance(40);
+    huc3_command(&mut memory, 0x60);
+    memory.write(0x0000, 0x0A);
+    let save = memory.cartridge().unwrap().save_data();
+
+    clock.advance(30);
+    let mut memory = huc3_with_clock(&clock);
+    memory.cartridge_mut().unwrap().load_save_data(&save);
+    huc3_command(&mut memory, 0x60);
+    huc3_command(&mut memory, 0x40);
+    let nibbles: Vec<u8> = (0..6).map(|_| huc3_command(&mut memory, 0x10) & 0x0F).collect();
+    assert_eq!(nibbles, vec![0x1, 0x0, 0x0, 0x3, 0x2, 0x1]);
+
+    // tone and ir show up as events
+    huc3_command(&mut memory, 0x6E);
+    memory.write(0x0000, 0x0E);
+    memory.write(0xA000, 0x01);
+    let events = memory.cartridge_mut().unwrap().take_events();
+    assert_eq!(events, vec![CartridgeEvent::Tone(0), CartridgeEvent::Infrared(true)]);
+}
+
+// clocks one bit into the 93LC56 and returns DO after the rising edge
+fn eeprom_bit(memory: &mut Memory, bit: bool) -> bool {
+    let di = if bit { 0x02 } else { 0x00 };
+    memory.write(0xA080, 0x80 | di);
+    memory.write(0xA080, 0xC0 | di);
+    memory.read(0xA080) & 0x01 != 0
+}
+
+fn eeprom_command(memory: &mut Memory, command: u32, bits: u8) -> u16 {
+    memory.write(0xA080, 0x00);
+    memory.write(0xA080, 0x80);
+    let mut out = 0;
+    for i in (0..bits).rev() {
+        out = out << 1 | eeprom_bit(memory, command >> i & 1 != 0) as u16;
+    }
+    out
+}
+
+fn eeprom_read(memory: &mut Memory, address: u8) -> u16 {
+    eeprom_command(memory, 0b110 << 8 | address as u32, 11);
+    (0..16).fold(0, |word, _| word << 1 | eeprom_bit(memory, false) as u16)
+}
+
+#[test]
+fn mbc7() {
+    let mut memory = memory_with(make_rom(0x22, 0x06, 0x00));
+    assert_eq!(memory.cartridge().unwrap().ram().len(), 256);
+    // blank eeprom without a save
+    assert!(memory.cartridge().unwrap().ram().iter().all(|byte| *byte == 0xFF));
+    memory.write(0x2000, 0x45);
+    assert_eq!(memory.read(0x4000), 0x45);
+
+    // needs both enables
+    memory.write(0x0000, 0x0A);
+    assert_eq!(memory.read(0xA080), 0xFF);
+    memory.write(0x4000, 0x40);
+
+    // accelerometer latches after 0x55 then 0xAA
+    memory.cartridge_mut().unwrap().set_tilt(1.0, -0.5);
+    memory.write(0xA000, 0x55);
+    assert_eq!(memory.read(0xA020), 0x00);
+    assert_eq!(memory.read(0xA030), 0x80);
+    memory.write(0xA010, 0xAA);
+    let x = memory.read(0xA020) as u16 | (memory.read(0xA030) as u16) << 8;
+    let y = memory.read(0xA040) as u16 | (memory.read(0xA050) as u16) << 8;
+    assert_eq!(x, 0x81D0 + 0x70);
+    assert_eq!(y, 0x81D0 - 0x38);
+    memory.cartridge_mut().unwrap().set_tilt(0.0, 0.0);
+    assert_eq!(memory.read(0xA020), 0x40);
+
+    // writes are ignored until EWEN
+    eeprom_command(&mut memory, (0b101 << 8 | 0x05) << 16 | 0x1234, 27);
+    assert_eq!(eeprom_read(&mut memory, 0x05), 0xFFFF);
+
+    eeprom_command(&mut memory, 0b100_1100_0000, 11);
+    eeprom_command(&mut memory, (0b101 << 8 | 0x05) << 16 | 0x1234, 27);
+    assert_eq!(eeprom_read(&mut memory, 0x05), 0x1234);
+    assert_eq!(memory.cartridge().unwrap().ram()[10..12], [0x34, 0x12]);
+
+    // ERASE, then WRAL
+    eeprom_command(&mut memory, 0b111 << 8 | 0x05, 11);
+    assert_eq!(eeprom_read(&mut memory, 0x05), 0xFFFF);
+    eeprom_command(&mut memory, 0b100_0100_0000 << 16 | 0xBEEF, 27);
+    assert_eq!(eeprom_read(&mut memory, 0x7F), 0xBEEF);
+
+    // EWDS protects it again
+    eeprom_command(&mut memory, 0b100_0000_0000, 11);
+    eeprom_command(&mut memory, 0b100_1000_0000, 11);
+    assert_eq!(eeprom_read(&mut memory, 0x00), 0xBEEF);
+    assert!(memory.cartridge().unwrap().is_dirty());
+}
@@ -0,0 +1,236 @@
+use cassowary_gb::console::memory::*;
+use cassowary_gb::console::regids::*;
+use cassowary_gb::console::interrupts::*;
+use cassowary_gb::console::power::*;
+
+#[test]
+fn regions() {
+    let mut memory = Memory::new(8 * KBYTE);
+
+    // echo ram mirrors wram both ways
+    memory.write(0xC123, 0x12);
+    assert_eq!(memory.read(0xE123), 0x12);
+    memory.write(0xFDFF, 0x34);
+    assert_eq!(memory.read(0xDDFF), 0x34);
+
+    // vram and oam
+    memory.write(0x8000, 0x56);
+    memory.write(0x9FFF, 0x57);
+    memory.write(0xFE00, 0x58);
+    memory.write(0xFE9F, 0x59);
+    assert_eq!(memory.ppu.read(0x8000), 0x56);
+    assert_eq!(memory.read(0x9FFF), 0x57);
+    assert_eq!(memory.ppu.read(0xFE00), 0x58);
+    assert_eq!(memory.read(0xFE9F), 0x59);
+
+    // writes to the unusable area go nowhere
+    memory.write(0xFEA0, 0x12);
+    assert_ne!(memory.read(0xFEA0), 0x12);
+
+    memory.write(0xFF80, 0x9A);
+    memory.write(0xFFFE, 0x9B);
+    memory.write(IE, 0x1F);
+    assert_eq!(memory.read(0xFF80), 0x9A);
+    assert_eq!(memory.read(0xFFFE), 0x9B);
+    assert_eq!(memory.read(IE), 0x1F);
+}
+
+#[test]
+fn device_registers() {
+    let mut memory = Memory::new(8 * KBYTE);
+
+    // writing DIV resets it no matter the value
+    for _ in 0..256 * 3 {
+        memory.tick();
+    }
+    assert_eq!(memory.read(DIV), 2);
+    memory.write(DIV, 0x55);
+    assert_eq!(memory.read(DIV), 0);
+
+    // timer overflow requests the interrupt through IF
+    memory.write(TMA, 0x80);
+    memory.write(TIMA, 0xFF);
+    memory.write(TAC, 0b101);
+    for _ in 0..32 {
+        if memory.read(IF) & TIMER_I != 0 {
+            break;
+        }
+        memory.tick();
+    }
+    assert_eq!(memory.read(IF) & TIMER_I, TIMER_I);
+    assert_eq!(memory.read(TIMA), 0x80);
+
+    // joypad only keeps the select bits, nothing pressed reads high
+    memory.write(0xFF00, 0xEF);
+    assert_eq!(memory.read(0xFF00) & 0x3F, 0x2F);
+
+    // stat mode and coincidence bits are read only
+    let ly = memory.read(0xFF44);
+    memory.write(0xFF45, ly);
+    memory.write(0xFF41, 0xFF);
+    assert_eq!(memory.read(0xFF41) & 0b0111_1100, 0b0111_1100);
+    memory.write(0xFF45, ly.wrapping_add(1));
+    assert_eq!(memory.read(0xFF41) & 0b0111_1100, 0b0111_1000);
+    memory.write(0xFF44, ly.wrapping_add(0x20));
+    assert_eq!(memory.read(0xFF44), ly);
+
+    memory.write(0xFF47, 0xE4);
+    assert_eq!(memory.read(0xFF47), 0xE4);
+}
+
+#[test]
+fn read_masks() {
+    let mut memory = Memory::new(8 * KBYTE);
+
+    for addr in [0xFEA0, 0xFEFF, 0xFF03, 0xFF08, 0xFF15, 0xFF27, 0xFF4C, 0xFF50, 0xFF7F] {
+        memory.write(addr, 0x00);
+        assert_eq!(memory.read(addr), 0xFF, "{addr:04X}");
+    }
+
+    memory.write(IF, 0x00);
+    assert_eq!(memory.read(IF), 0xE0);
+    memory.write(TAC, 0x00);
+    assert_eq!(memory.read(TAC), 0xF8);
+    memory.write(0xFF00, 0x30);
+    assert_eq!(memory.read(0xFF00), 0xFF);
+    memory.write(0xFF02, 0x00);
+    assert_eq!(memory.read(0xFF02), 0x7E);
+    memory.write(0xFF41, 0x00);
+    assert_eq!(memory.read(0xFF41) & 0x80, 0x80);
+
+    // NR11 duty is readable, the length is write only
+    memory.write(0xFF11, 0x80);
+    assert_eq!(memory.read(0xFF11), 0xBF);
+    memory.write(0xFF13, 0x12);
+    assert_eq!(memory.read(0xFF13), 0xFF);
+    memory.write(0xFF30, 0x12);
+    assert_eq!(memory.read(0xFF30), 0x12);
+
+    memory.write(0xFF46, 0xC0);
+    assert_eq!(memory.read(0xFF46), 0xC0);
+}
+
+#[test]
+fn oam_dma() {
+    let mut memory = Memory::new(8 * KBYTE);
+    for i in 0..0xA0 {
+        memory.write(0xC100 + i, i as u8 + 1);
+    }
+    memory.write(0xFF80, 0x42);
+
+    memory.write(0xFF46, 0xC1);
+    // nothing moves before the startup delay is over
+    for _ in 0..4 {
+        memory.tick();
+    }
+    assert_eq!(memory.read(0xC100), 0x01);
+
+    for _ in 0..4 * 4 {
+        memory.tick();
+    }
+    // only io, hram and IE are reachable for the cpu and oam reads high for the ppu
+    assert_eq!(memory.read(0xC100), 0xFF);
+    assert_eq!(memory.read(0xFF80), 0x42);
+    assert_eq!(memory.ppu.read(0xFE00), 0xFF);
+    memory.write(0xC000, 0x12);
+    memory.write(IE, 0x04);
+    assert_eq!(memory.read(IE), 0x04);
+    assert_eq!(memory.read(IF) & 0x1F, 0x00);
+
+    for _ in 0..160 * 4 {
+        memory.tick();
+    }
+    assert_eq!(memory.read(0xC000), 0x00);
+    for i in 0..0xA0 {
+        assert_eq!(memory.read(0xFE00 + i), i as u8 + 1);
+    }
+}
+
+#[test]
+fn oam_dma_echo_source() {
+    let mut memory = Memory::new(8 * KBYTE);
+    memory.write(0xDE00, 0x12);
+    memory.write(0xDE9F, 0x34);
+
+    // 0xFE00 isn't readable as a source, it comes from wram instead
+    memory.write(0xFF46, 0xFE);
+    for _ in 0..170 * 4 {
+        memory.tick();
+    }
+    assert_eq!(memory.read(0xFE00), 0x12);
+    assert_eq!(memory.read(0xFE9F), 0x34);
+}
+
+#[test]
+fn boot_rom_overlay() {
+    let mut memory = Memory::new(0x8000);
+    for addr in 0..0x1000 {
+        memory.write(addr, 0x11);
+    }
+
+    // anything shorter would be read past its end
+    assert!(memory.load_boot_rom(vec![0x22; 0x80]).is_err());
+    assert!(!memory.boot_rom_mapped());
+    memory.load_boot_rom(vec![0x22; 0x100]).unwrap();
+    assert_eq!(memory.read(0x0000), 0x22);
+    assert_eq!(memory.read(0x00FF), 0x22);
+    assert_eq!(memory.read(0x0100), 0x11);
+    assert_eq!(memory.read(0x0200), 0x11);
+
+    // writing 0 doesn't unmap it, anything else does for good
+    memory.write(0xFF50, 0x00);
+    assert!(memory.boot_rom_mapped());
+    memory.write(0xFF50, 0x01);
+    assert!(!memory.boot_rom_mapped());
+    assert_eq!(memory.read(0x0000), 0x11);
+
+    memory.load_boot_rom(vec![0x33; 0x900]).unwrap();
+    assert_eq!(memory.read(0x00FF), 0x33);
+    assert_eq!(memory.read(0x0100), 0x11);
+    assert_eq!(memory.read(0x01FF), 0x11);
+    assert_eq!(memory.read(0x0200), 0x33);
+    assert_eq!(memory.read(0x08FF), 0x33);
+    assert_eq!(memory.read(0x0900), 0x11);
+}
+
+fn ram_contents(memory: &Memory) -> Vec<u8> {
+    let wram = (0xC000..=0xDFFF).map(|addr| memory.read(addr));
+    let oam = (0xFE00..=0xFE9F).map(|addr| memory.read(addr));
+    let hram = (0xFF80..=0xFFFE).map(|addr| memory.read(addr));
+    wram.chain(oam).chain(hram).collect()
+}
+
+#[test]
+fn power_on_fills() {
+    let mut memory = Memory::new(8 * KBYTE);
+    assert!(ram_contents(&memory).iter().all(|byte| *byte == 0x00));
+
+    memory.power_on(&PowerOnState::new(PowerOnFill::Ones, None));
+    assert!(ram_contents(&memory).iter().all(|byte| *byte == 0xFF));
+    memory.power_on(&PowerOnState::new(PowerOnFill::Zeros, None));
+    assert!(ram_contents(&memory).iter().all(|byte| *byte == 0x00));
+
+    // the same seed gives the same ram, another seed doesn't
+    let state = PowerOnState::new(PowerOnFill::Random, Some(1234));
+    assert_eq!(state.seed, 1234);
+    memory.power_on(&state);
+    let random = ram_contents(&memory);
+    let mut other = Memory::new(8 * KBYTE);
+    other.power_on(&state);
+    assert_eq!(ram_contents(&other), random);
+    other.power_on(&PowerOnState::new(PowerOnFill::Random, Some(1235)));
+    assert_ne!(ram_contents(&other), random);
+    let zeros = random.iter().filter(|byte| **byte == 0).count();
+    assert!(zeros < random.len() / 64, "{zeros} zero bytes");
+
+    // stripes of mostly clear and mostly set bits
+    memory.power_on(&PowerOnState::new(PowerOnFill::HardwareLike, Some(0)));
+    let ones = |range: std::ops::Range<u16>| range.map(|addr| memory.read(addr).count_ones()).sum::<u32>();
+    assert!(ones(0xC000..0xC080) < 0x80 * 8 / 8);
+    assert!(ones(0xC080..0xC100) > 0x80 * 8 * 7 / 8);
+
+    let fill: PowerOnFill = "hardware".parse().unwrap();
+    assert_eq!(fill, PowerOnFill::HardwareLike);
+    assert_eq!(fill.to_string(), "hardware");
+    assert!("noise".parse::<PowerOnFill>().is_err());
+}
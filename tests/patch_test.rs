@@ -0,0 +1,209 @@
+mod common;
+
+use cassowary_gb::console::loader::*;
+use cassowary_gb::console::patch::*;
+use cassowary_gb::console::*;
+use common::*;
+
+use std::fs;
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cassowary-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn varint(mut value: usize, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte | 0x80);
+            return;
+        }
+        out.push(byte);
+        value -= 1;
+    }
+}
+
+// source, target and patch crc32 footer
+fn finish(mut patch: Vec<u8>, source: &[u8], target: &[u8]) -> Vec<u8> {
+    patch.extend(crc32fast::hash(source).to_le_bytes());
+    patch.extend(crc32fast::hash(target).to_le_bytes());
+    patch.extend(crc32fast::hash(&patch).to_le_bytes());
+    patch
+}
+
+fn ups(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut patch = b"UPS1".to_vec();
+    varint(source.len(), &mut patch);
+    varint(target.len(), &mut patch);
+    let mut last = 0;
+    let mut i = 0;
+    while i < target.len() {
+        let xor = |i: usize| source.get(i).copied().unwrap_or(0) ^ target[i];
+        if xor(i) == 0 {
+            i += 1;
+            continue;
+        }
+        varint(i - last, &mut patch);
+        while i < target.len() && xor(i) != 0 {
+            patch.push(xor(i));
+            i += 1;
+        }
+        patch.push(0);
+        i += 1;
+        last = i;
+    }
+    finish(patch, source, target)
+}
+
+#[test]
+fn ips() {
+    let rom = [0u8; 8];
+    let mut patch = b"PATCH".to_vec();
+    patch.extend([0x00, 0x00, 0x01, 0x00, 0x02, 0xAA, 0xBB]);
+    // run of 3 0x55 that grows the rom
+    patch.extend([0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x03, 0x55]);
+    patch.extend(b"EOF");
+    assert_eq!(apply(&rom, &patch).unwrap(), [0, 0xAA, 0xBB, 0, 0, 0, 0, 0x55, 0x55, 0x55]);
+
+    // size after EOF truncates
+    patch.extend([0x00, 0x00, 0x04]);
+    assert_eq!(apply(&rom, &patch).unwrap(), [0, 0xAA, 0xBB, 0]);
+
+    assert!(matches!(apply(&rom, b"PATCH\x00\x00"), Err(PatchError::Malformed(_))));
+    assert!(matches!(apply(&rom, b"NOPE"), Err(PatchError::UnknownFormat)));
+}
+
+#[test]
+fn ups_checksums() {
+    let source = [1u8, 2, 3, 4, 5, 6];
+    let target = [1u8, 9, 3, 4, 7, 6, 8];
+    let patch = ups(&source, &target);
+    assert_eq!(apply(&source, &patch).unwrap(), target);
+
+    // wrong rom
+    let result = apply(&[1u8, 2, 3, 4, 5, 0], &patch);
+    assert!(matches!(result, Err(PatchError::Checksum { which: "source", .. })));
+
+    let mut corrupt = patch.clone();
+    corrupt[6] ^= 1;
+    assert!(matches!(apply(&source, &corrupt), Err(PatchError::Checksum { which: "patch", .. })));
+}
+
+#[test]
+fn malformed_sizes() {
+    let source = [0u8; 4];
+    // continuation bytes past what fits in a usize
+    let mut patch = b"UPS1".to_vec();
+    patch.extend([0x00; 12]);
+    let patch = finish(patch, &source, &source);
+    assert!(matches!(apply(&source, &patch), Err(PatchError::Malformed(_))));
+
+    // a target too big for any cartridge
+    let mut patch = b"BPS1".to_vec();
+    varint(source.len(), &mut patch);
+    varint(1 << 40, &mut patch);
+    varint(0, &mut patch);
+    let patch = finish(patch, &source, &source);
+    assert!(matches!(apply(&source, &patch), Err(PatchError::Malformed(_))));
+
+    // a target copy reading what it writes would grow forever without the target size
+    let mut patch = b"BPS1".to_vec();
+    varint(source.len(), &mut patch);
+    varint(source.len(), &mut patch);
+    varint(0, &mut patch);
+    varint(0, &mut patch);
+    varint(((1 << 40) - 1) << 2 | 3, &mut patch);
+    varint(0, &mut patch);
+    let patch = finish(patch, &source, &source);
+    assert!(matches!(apply(&source, &patch), Err(PatchError::Malformed(_))));
+
+    // relative offsets that add up past an isize
+    let mut patch = b"BPS1".to_vec();
+    varint(source.len(), &mut patch);
+    varint(source.len(), &mut patch);
+    varint(0, &mut patch);
+    varint(2, &mut patch);
+    varint(0, &mut patch);
+    varint(2, &mut patch);
+    varint(usize::MAX - 1, &mut patch);
+    let patch = finish(patch, &source, &source);
+    assert!(matches!(apply(&source, &patch), Err(PatchError::Malformed(_))));
+
+    // ups skips that add up past a usize
+    let mut patch = b"UPS1".to_vec();
+    varint(source.len(), &mut patch);
+    varint(source.len(), &mut patch);
+    varint(usize::MAX - 1, &mut patch);
+    patch.push(0);
+    varint(5, &mut patch);
+    patch.push(0);
+    let patch = finish(patch, &source, &source);
+    assert!(matches!(apply(&source, &patch), Err(PatchError::Malformed(_))));
+}
+
+#[test]
+fn bps_commands() {
+    let source = b"ABCDEFGH".to_vec();
+    let target = b"ABCDxyEFxyEFxy".to_vec();
+    let mut patch = b"BPS1".to_vec();
+    varint(source.len(), &mut patch);
+    varint(target.len(), &mut patch);
+    varint(3, &mut patch);
+    patch.extend(b"abc");
+    // SourceRead ABCD
+    varint((4 - 1) << 2, &mut patch);
+    // TargetRead xy
+    varint((2 - 1) << 2 | 1, &mut patch);
+    patch.extend(b"xy");
+    // SourceCopy EF from offset 4
+    varint((2 - 1) << 2 | 2, &mut patch);
+    varint(4 << 1, &mut patch);
+    // TargetCopy xyEFxy from offset 4, reading bytes it just wrote
+    varint((6 - 1) << 2 | 3, &mut patch);
+    varint(4 << 1, &mut patch);
+    let patch = finish(patch, &source, &target);
+    assert_eq!(apply(&source, &patch).unwrap(), target);
+
+    let result = apply(b"ABCDEFGX", &patch);
+    assert!(matches!(result, Err(PatchError::Checksum { which: "source", .. })));
+}
+
+#[test]
+fn patches_applied_at_load() {
+    let dir = temp_dir("patch-load");
+    let rom = make_rom(0x00, 0x00, 0x00);
+    let rom_path = dir.join("game.gb");
+    fs::write(&rom_path, &rom).unwrap();
+
+    let mut target = rom.clone();
+    target[0x0200] = 0x42;
+    fs::write(dir.join("game.ups"), ups(&rom, &target)).unwrap();
+
+    let mut gb = GameBoy::new(false);
+    gb.load_rom(rom_path.clone()).unwrap();
+    assert_eq!(gb.gamepack.lock().unwrap().read(0x0200), 0x42);
+    assert_eq!(fs::read(&rom_path).unwrap(), rom);
+
+    // the same hack shipped as both ips and ups is only applied once
+    fs::write(dir.join("game.ips"), b"PATCH\x00\x02\x00\x00\x01\x42EOF").unwrap();
+    let mut gb = GameBoy::new(false);
+    gb.load_rom(rom_path.clone()).unwrap();
+    assert_eq!(gb.gamepack.lock().unwrap().read(0x0200), 0x42);
+    fs::remove_file(dir.join("game.ips")).unwrap();
+
+    // given patches replace the ones next to the rom
+    let explicit = dir.join("other.ips");
+    fs::write(&explicit, b"PATCH\x00\x02\x00\x00\x01\x24EOF").unwrap();
+    let mut gb = GameBoy::new(false);
+    gb.set_patches(vec![explicit]);
+    gb.load_rom(rom_path.clone()).unwrap();
+    assert_eq!(gb.gamepack.lock().unwrap().read(0x0200), 0x24);
+
+    fs::write(dir.join("game.ups"), ups(&target, &rom)).unwrap();
+    let mut gb = GameBoy::new(false);
+    assert!(matches!(gb.load_rom(rom_path), Err(LoadError::Patch(_, PatchError::Checksum { .. }))));
+}
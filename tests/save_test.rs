@@ -0,0 +1,100 @@
+mod common;
+
+use cassowary_gb::console::save::*;
+use cassowary_gb::console::*;
+use common::*;
+
+use std::fs;
+use std::path::PathBuf;
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cassowary-{name}-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn battery_ram_round_trip() {
+    let dir = temp_dir("save");
+    let rom_path = dir.join("game.gb");
+    fs::write(&rom_path, make_rom(0x1B, 0x02, 0x02)).unwrap();
+
+    let mut gb = GameBoy::new(false);
+    gb.load_rom(rom_path.clone()).unwrap();
+    {
+        let mut memory = gb.gamepack.lock().unwrap();
+        memory.write(0x0000, 0x0A);
+        memory.write(0xA000, 0x12);
+        memory.write(0xBFFF, 0x34);
+    }
+    gb.flush_save().unwrap();
+
+    let save = fs::read(dir.join("game.sav")).unwrap();
+    assert_eq!(save.len(), 0x2000);
+    assert_eq!(save[0x0000], 0x12);
+    assert_eq!(save[0x1FFF], 0x34);
+    assert!(!dir.join("game.sav.tmp").exists());
+
+    let mut gb = GameBoy::new(false);
+    gb.load_rom(rom_path).unwrap();
+    let mut memory = gb.gamepack.lock().unwrap();
+    memory.write(0x0000, 0x0A);
+    assert_eq!(memory.read(0xA000), 0x12);
+    assert_eq!(memory.read(0xBFFF), 0x34);
+    drop(memory);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failed_save_is_retried() {
+    let dir = temp_dir("saveretry");
+    let rom_path = dir.join("game.gb");
+    fs::write(&rom_path, make_rom(0x1B, 0x02, 0x02)).unwrap();
+
+    let mut gb = GameBoy::new(false);
+    gb.load_rom(rom_path).unwrap();
+    gb.gamepack.lock().unwrap().write(0x0000, 0x0A);
+    gb.gamepack.lock().unwrap().write(0xA000, 0x12);
+
+    // nowhere to write to, the ram stays dirty
+    fs::remove_dir_all(&dir).unwrap();
+    assert!(gb.flush_save().is_err());
+    assert!(gb.gamepack.lock().unwrap().cartridge().unwrap().is_dirty());
+
+    fs::create_dir_all(&dir).unwrap();
+    gb.flush_save().unwrap();
+    assert!(!gb.gamepack.lock().unwrap().cartridge().unwrap().is_dirty());
+    assert_eq!(fs::read(dir.join("game.sav")).unwrap()[0], 0x12);
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn no_save_without_battery() {
+    let dir = temp_dir("nobattery");
+    let rom_path = dir.join("game.gb");
+    fs::write(&rom_path, make_rom(0x1A, 0x02, 0x02)).unwrap();
+
+    let mut gb = GameBoy::new(false);
+    gb.load_rom(rom_path).unwrap();
+    gb.gamepack.lock().unwrap().write(0x0000, 0x0A);
+    gb.gamepack.lock().unwrap().write(0xA000, 0x12);
+    gb.flush_save().unwrap();
+    assert!(!dir.join("game.sav").exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn atomic_write() {
+    let dir = temp_dir("atomic");
+    let save = SaveFile::for_rom(&dir.join("game.gbc"));
+    assert_eq!(save.path(), dir.join("game.sav"));
+    assert_eq!(save.load().unwrap(), None);
+    save.write(&[1, 2, 3]).unwrap();
+    save.write(&[4, 5]).unwrap();
+    assert_eq!(save.load().unwrap(), Some(vec![4, 5]));
+
+    fs::remove_dir_all(dir).unwrap();
+}